@@ -0,0 +1,210 @@
+// Compute-shader versions of the noise, normalise and falloff stages, run by `GpuTerrainGenerator`.
+// Each invocation handles one cell of the height map, stored row by row.
+
+struct Parameters {
+    size: vec2<u32>,
+    // Number of noise layers, or of noisy radius harmonics
+    count: u32,
+    shape: u32,
+    // Falloff centre and radius, in cells
+    centre: vec2<f32>,
+    radius: f32,
+    // Cosine and sine of the falloff rotation
+    rotation: vec2<f32>,
+    shape_parameters: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> parameters: Parameters;
+@group(0) @binding(1) var<storage, read_write> heights: array<f32>;
+// Noise layers as (cells x, cells y, weight, gradient offset), followed by their gradients,
+// or the phases of a noisy radius
+@group(0) @binding(2) var<storage, read> data: array<f32>;
+// Largest height and bitwise inverted smallest height, as ordered keys
+@group(0) @binding(3) var<storage, read_write> range: array<atomic<u32>, 2>;
+
+const GAUSSIAN = 0u;
+const QUADRATIC = 1u;
+const SQUIRCLE = 2u;
+const ELLIPSE = 3u;
+const NOISY_RADIUS = 4u;
+const RING = 5u;
+const CRESCENT = 6u;
+
+const EPSILON = 1.1920929e-7;
+
+fn cell_index(cell: vec2<u32>) -> u32 {
+    return cell.y * parameters.size.x + cell.x;
+}
+
+fn in_bounds(cell: vec2<u32>) -> bool {
+    return cell.x < parameters.size.x && cell.y < parameters.size.y;
+}
+
+fn smoothstep_weight(t: f32) -> f32 {
+    return t * t * (3.0 - 2.0 * t);
+}
+
+fn lattice_gradient(layer: u32, x: i32, y: i32) -> vec2<f32> {
+    let cells = vec2<i32>(i32(data[layer * 4u]), i32(data[layer * 4u + 1u]));
+    let offset = u32(data[layer * 4u + 3u]);
+    let wrapped = vec2<i32>(((x % cells.x) + cells.x) % cells.x, ((y % cells.y) + cells.y) % cells.y);
+    let index = offset + 2u * u32(wrapped.y * cells.x + wrapped.x);
+    return vec2<f32>(data[index], data[index + 1u]);
+}
+
+fn sample_layer(layer: u32, uv: vec2<f32>) -> f32 {
+    let cells = vec2<f32>(data[layer * 4u], data[layer * 4u + 1u]);
+    let position = uv * cells;
+    let corner = floor(position);
+    let left = i32(corner.x);
+    let top = i32(corner.y);
+    let f = position - corner;
+
+    let top_left = dot(lattice_gradient(layer, left, top), f);
+    let top_right = dot(lattice_gradient(layer, left + 1, top), f - vec2<f32>(1.0, 0.0));
+    let bottom_left = dot(lattice_gradient(layer, left, top + 1), f - vec2<f32>(0.0, 1.0));
+    let bottom_right = dot(lattice_gradient(layer, left + 1, top + 1), f - vec2<f32>(1.0, 1.0));
+
+    let sx = smoothstep_weight(f.x);
+    let sy = smoothstep_weight(f.y);
+    let top_value = top_left * (1.0 - sx) + top_right * sx;
+    let bottom_value = bottom_left * (1.0 - sx) + bottom_right * sx;
+    return top_value * (1.0 - sy) + bottom_value * sy;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn noise(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.xy;
+    if !in_bounds(cell) {
+        return;
+    }
+    let uv = vec2<f32>(cell) / vec2<f32>(parameters.size);
+    var value = 0.0;
+    for (var layer = 0u; layer < parameters.count; layer++) {
+        value += sample_layer(layer, uv) * data[layer * 4u + 2u];
+    }
+    heights[cell_index(cell)] = value;
+}
+
+fn gaussian(distance: f32) -> f32 {
+    return exp(-0.5 * distance * distance);
+}
+
+fn quadratic(distance: f32) -> f32 {
+    return max(1.0 - distance * distance, 0.0);
+}
+
+fn falloff_mask(offset: vec2<f32>) -> f32 {
+    let a = parameters.shape_parameters.x;
+    let b = parameters.shape_parameters.y;
+    let rotation = parameters.rotation;
+    switch parameters.shape {
+        case QUADRATIC: {
+            return quadratic(length(offset));
+        }
+        case SQUIRCLE: {
+            let exponent = max(a, EPSILON);
+            let distance = pow(pow(abs(offset.x), exponent) + pow(abs(offset.y), exponent), 1.0 / exponent);
+            return quadratic(distance);
+        }
+        case ELLIPSE: {
+            let rotated = vec2<f32>(
+                rotation.x * offset.x - rotation.y * offset.y,
+                rotation.y * offset.x + rotation.x * offset.y
+            );
+            return gaussian(length(rotated / vec2<f32>(max(a, EPSILON), 1.0)));
+        }
+        case NOISY_RADIUS: {
+            let angle = atan2(offset.y, offset.x);
+            var wobble = 0.0;
+            var total = 0.0;
+            for (var harmonic = 1u; harmonic <= parameters.count; harmonic++) {
+                let weight = 1.0 / f32(harmonic);
+                wobble += weight * sin(f32(harmonic) * angle + data[harmonic - 1u]);
+                total += weight;
+            }
+            if total > 0.0 {
+                wobble /= total;
+            }
+            let radius = max(1.0 + a * wobble, EPSILON);
+            return gaussian(length(offset) / radius);
+        }
+        case RING: {
+            return gaussian((length(offset) - a) / max(b, EPSILON));
+        }
+        case CRESCENT: {
+            let bite_centre = rotation * a;
+            let bite = quadratic(max(b, EPSILON) / max(length(offset - bite_centre), EPSILON));
+            return quadratic(length(offset)) * bite;
+        }
+        default: {
+            return gaussian(length(offset));
+        }
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn falloff(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.xy;
+    if !in_bounds(cell) {
+        return;
+    }
+    let offset = (vec2<f32>(cell) - parameters.centre) / parameters.radius;
+    heights[cell_index(cell)] *= falloff_mask(offset);
+}
+
+// Unsigned key which sorts in the same order as the float
+fn ordered_key(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    if (bits & 0x80000000u) != 0u {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+fn from_ordered_key(key: u32) -> f32 {
+    if (key & 0x80000000u) != 0u {
+        return bitcast<f32>(key & 0x7fffffffu);
+    }
+    return bitcast<f32>(~key);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn measure(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.xy;
+    if !in_bounds(cell) {
+        return;
+    }
+    let key = ordered_key(heights[cell_index(cell)]);
+    atomicMax(&range[0], key);
+    atomicMax(&range[1], ~key);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn normalise(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.xy;
+    if !in_bounds(cell) {
+        return;
+    }
+    let max_value = from_ordered_key(atomicLoad(&range[0]));
+    let min_value = from_ordered_key(~atomicLoad(&range[1]));
+    let extent = max_value - min_value;
+    let index = cell_index(cell);
+    if extent <= 0.0 {
+        heights[index] = 0.0;
+    } else {
+        heights[index] = (heights[index] - min_value) / extent;
+    }
+}
+
+// Height map texture to draw from, written once the stages have run
+@group(1) @binding(0) var height_texture: texture_storage_2d<r32float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn store(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = id.xy;
+    if !in_bounds(cell) {
+        return;
+    }
+    textureStore(height_texture, cell, vec4<f32>(heights[cell_index(cell)], 0.0, 0.0, 1.0));
+}
@@ -1,108 +1,203 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
+#import bevy_sprite::mesh2d_view_bindings::globals
 #import "shaders/settings.wgsl"::COLOUR_MULTIPLIER
 
-@group(2) @binding(0) var<uniform> mouse_position: vec2<f32>;
+@group(2) @binding(0) var<uniform> sun_position: vec2<f32>;
 @group(2) @binding(1) var<uniform> quad_colour: vec4<f32>;
 @group(2) @binding(2) var height_map: texture_2d<f32>;
 @group(2) @binding(3) var height_map_sampler: sampler;
 @group(2) @binding(4) var colour_map: texture_2d<f32>;
 @group(2) @binding(5) var colour_map_sampler: sampler;
+@group(2) @binding(6) var shadow_map: texture_2d<f32>;
+@group(2) @binding(7) var shadow_map_sampler: sampler;
+@group(2) @binding(8) var<uniform> sun_direction: vec3<f32>;
+@group(2) @binding(9) var<uniform> sun_height: f32;
+@group(2) @binding(10) var<uniform> light_model: u32;
+
+@group(2) @binding(11) var ambient_occlusion: texture_2d<f32>;
+@group(2) @binding(12) var ambient_occlusion_sampler: sampler;
+@group(2) @binding(13) var<uniform> terrain_relief: f32;
+@group(2) @binding(14) var<uniform> hillshade: u32;
+@group(2) @binding(15) var<uniform> sea_level: f32;
+
+struct Water {
+    deep_colour: vec4<f32>,
+    bed_colour: vec4<f32>,
+    foam_colour: vec4<f32>,
+    depth: f32,
+    wave_scale: f32,
+    wave_speed: f32,
+    wave_strength: f32,
+    glint_strength: f32,
+    glint_shininess: f32,
+    foam_depth: f32,
+}
 
-const SUN_HEIGHT = 1.5;
-
+@group(2) @binding(16) var<uniform> water: Water;
 
-fn blocked_line_of_sight(start: vec2<i32>, end: vec2<i32>, texture_dimensions: vec2<u32>) -> f32 {
-    let fx = 1.0 / f32(texture_dimensions.x);
-    let fy = 1.0 / f32(texture_dimensions.y);
+const DIRECTIONAL_LIGHT = 0u;
+const POINT_LIGHT = 1u;
 
-    var x0: i32 = start.x;
-    var y0: i32 = start.y;
-    var h0: f32 = textureSample(height_map, height_map_sampler, vec2<f32>(f32(x0) * fx, f32(y0) * fy)).x;
-    let x1: i32 = end.x;
-    let y1: i32 = end.y;
-    let h1: f32 = SUN_HEIGHT;
+const HILLSHADE_OFF = 0u;
+const HILLSHADE_SUN = 1u;
+const HILLSHADE_MULTIDIRECTIONAL = 2u;
 
-    let max_distance: f32 = sqrt(f32((abs(x1 - x0) * abs(x1 - x0)) + (abs(y1 - y0) * abs(y1 - y0))));
-    let dh: f32 = (h1 - h0) / max_distance;
+// Darkness of full shadow, without hillshading
+const SHADOW_STRENGTH = 0.3;
+// Share of the light which comes from the whole sky rather than from the sun
+const AMBIENT = 0.35;
 
-    var dx: i32 = abs(x1 - x0);
-    var sx = -1;
-    if x0 < x1 {
-        sx = 1;
-    }
+const TAU = 6.283185307;
 
-    var dy: i32 = -abs(y1 - y0);
-    var sy = -1;
-    if y0 < y1 {
-        sy = 1;
-    }
-    var error: i32 = dx + dy;
+// Height of the ground at a cell, with cells past the edges clamped to them
+fn ground_height(cell: vec2<i32>) -> f32 {
+    let last = vec2<i32>(textureDimensions(height_map)) - 1;
+    return textureLoad(height_map, clamp(cell, vec2<i32>(0), last), 0).x;
+}
 
-    loop {
-        let distance = sqrt(f32((abs(x0 - start.x) * abs(x0 - start.x)) + (abs(y0 - start.y) * abs(y0 - start.y))));
-        let ray_height = h0 + (dh * distance);
+// Height of a cell as shaded, with water flattened to its surface
+fn surface_height(cell: vec2<i32>) -> f32 {
+    return max(ground_height(cell), sea_level);
+}
 
-        let position = vec2<f32>(f32(x0) * fx, f32(y0) * fy);
-        let height = textureSample(height_map, height_map_sampler, position);
-        if height.x > (ray_height + 0.01) {
-            let d = distance / max_distance;
-            return max(0.0, 0.3 - (d *  d));
-        }
+// Cells spanned by one unit of height
+fn vertical_scale() -> f32 {
+    return terrain_relief * f32(textureDimensions(height_map).x);
+}
 
-        if (x0 == x1 && y0 == y1) {
-            break;
-        }
+// Upward unit normal for slopes in height units per cell, with x across the map, y down it and z up
+fn normal_from_slope(dx: f32, dy: f32) -> vec3<f32> {
+    let scale = vertical_scale();
+    return normalize(vec3<f32>(-dx * scale, -dy * scale, 1.0));
+}
 
-        let e2: i32 = 2 * error;
+fn surface_normal(cell: vec2<i32>) -> vec3<f32> {
+    let dx = surface_height(cell + vec2<i32>(1, 0)) - surface_height(cell - vec2<i32>(1, 0));
+    let dy = surface_height(cell + vec2<i32>(0, 1)) - surface_height(cell - vec2<i32>(0, 1));
+    return normal_from_slope(0.5 * dx, 0.5 * dy);
+}
 
-        if e2 >= dy {
-            if x0 == x1 {
-                break;
-            }
-            error += dy;
-            x0 += sx;
-        }
+// Normal of the ground itself, including the sea bed
+fn ground_normal(cell: vec2<i32>) -> vec3<f32> {
+    let dx = ground_height(cell + vec2<i32>(1, 0)) - ground_height(cell - vec2<i32>(1, 0));
+    let dy = ground_height(cell + vec2<i32>(0, 1)) - ground_height(cell - vec2<i32>(0, 1));
+    return normal_from_slope(0.5 * dx, 0.5 * dy);
+}
 
-        if e2 <= dx {
-            if y0 == y1 {
-                break;
-            }
-            error += dx;
-            y0 += sy;
-        }
+// Unit vector towards the sun from a point on the surface
+fn light_direction(uv: vec2<f32>, height: f32) -> vec3<f32> {
+    if light_model == POINT_LIGHT {
+        let across = (sun_position - uv) * vec2<f32>(textureDimensions(height_map));
+        return normalize(vec3<f32>(across, (sun_height - height) * vertical_scale()));
     }
+    return sun_direction;
+}
 
-    return 0.0;
+// Unit vector towards a light 45 degrees up, at an azimuth in degrees clockwise from the top of the map
+fn light_from(azimuth: f32) -> vec3<f32> {
+    let angle = radians(azimuth);
+    let elevation = radians(45.0);
+    return vec3<f32>(cos(elevation) * sin(angle), -cos(elevation) * cos(angle), sin(elevation));
 }
 
+fn lambert(normal: vec3<f32>, light: vec3<f32>) -> f32 {
+    return max(dot(normal, light), 0.0);
+}
 
-@fragment
-fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    var colour = COLOUR_MULTIPLIER;
+// Cartographic relief shading from several lights around the north west,
+// so slopes facing any way still show their shape
+fn multi_directional(normal: vec3<f32>) -> f32 {
+    return 0.2 * lambert(normal, light_from(225.0))
+        + 0.3 * lambert(normal, light_from(270.0))
+        + 0.3 * lambert(normal, light_from(315.0))
+        + 0.2 * lambert(normal, light_from(360.0));
+}
 
-    let texture_dimensions = textureDimensions(height_map).xy;
-    let sun_position_x = f32(texture_dimensions.x) * mouse_position.x;
-    let sun_position_y = f32(texture_dimensions.y) * mouse_position.y;
-    let sun_position_xi = i32(sun_position_x);
-    let sun_position_yi = i32(sun_position_x);
+// Slope of one sine wave travelling across the map, at most 1
+fn wave_slope(uv: vec2<f32>, direction: vec2<f32>, frequency: f32) -> vec2<f32> {
+    let wave_vector = normalize(direction) * frequency * water.wave_scale * TAU;
+    let phase = dot(wave_vector, uv) - globals.time * water.wave_speed * sqrt(frequency);
+    return normalize(direction) * cos(phase);
+}
 
-    let distance = distance(mouse_position, in.uv);
-    let sun_radius = 0.1;
-    if (distance < sun_radius) {
-        colour = vec4<f32>(1.0, 1.0, distance / sun_radius, 1.0);
-    }
+// Upward unit normal of the water surface, from a few waves crossing the map in different directions
+fn water_normal(uv: vec2<f32>) -> vec3<f32> {
+    let slope = 0.4 * wave_slope(uv, vec2<f32>(1.0, 0.35), 1.0)
+        + 0.3 * wave_slope(uv, vec2<f32>(-0.6, 1.0), 1.7)
+        + 0.2 * wave_slope(uv, vec2<f32>(0.2, -1.0), 2.9)
+        + 0.1 * wave_slope(uv, vec2<f32>(-1.0, -0.8), 4.3);
+    return normalize(vec3<f32>(-slope * water.wave_strength, 1.0));
+}
 
-    let start = vec2<i32>(i32(in.uv.x * f32(texture_dimensions.x)), i32(in.uv.y * f32(texture_dimensions.y)));
-    let end = vec2<i32>(i32(mouse_position.x * f32(texture_dimensions.x)), i32(mouse_position.y * f32(texture_dimensions.y)));
-    let shadow = blocked_line_of_sight(start, end, texture_dimensions);
+// Colour of water of the given depth, over its colour in the colour map at the shore
+fn water_colour(uv: vec2<f32>, cell: vec2<i32>, shallow: vec3<f32>, lightness: f32, visibility: f32) -> vec3<f32> {
+    let depth = max(sea_level - ground_height(cell), 0.0);
+    let deepness = clamp(depth / max(water.depth, 1e-6), 0.0, 1.0);
+
+    // The sea bed shows through shallow water, shaded by its own relief
+    let light = light_direction(uv, sea_level);
+    let bed_light = AMBIENT + (1.0 - AMBIENT) * lambert(ground_normal(cell), light) * visibility;
+    let bed = water.bed_colour.rgb * bed_light;
+    let colour = mix(shallow, water.deep_colour.rgb, deepness) * lightness;
+    var result = mix(bed, colour, deepness);
+
+    // Sun glints off the waves, seen from straight above
+    let halfway = normalize(light + vec3<f32>(0.0, 0.0, 1.0));
+    let glint = pow(max(dot(water_normal(uv), halfway), 0.0), water.glint_shininess);
+    result += vec3<f32>(water.glint_strength * glint * visibility);
+
+    // Foam along the shore, in bands washing in and out
+    let shore = clamp(1.0 - depth / max(water.foam_depth, 1e-6), 0.0, 1.0);
+    let bands = 0.6 + 0.4 * sin(TAU * (2.0 * depth / max(water.foam_depth, 1e-6) + globals.time * water.wave_speed / TAU));
+    result = mix(result, water.foam_colour.rgb, shore * bands * water.foam_colour.a);
+
+    return result;
+}
 
-    if shadow > 0.001 {
-        let lightness = 1.0 - shadow;
-        colour = vec4<f32>(lightness, lightness, lightness, 1.0);
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let cell = vec2<i32>(in.uv * vec2<f32>(textureDimensions(height_map)));
+    let height = surface_height(cell);
+
+    // Precomputed, so a single lookup whatever the distance to the sun
+    let visibility = textureSample(shadow_map, shadow_map_sampler, in.uv).x;
+    var occlusion = textureSample(ambient_occlusion, ambient_occlusion_sampler, in.uv).x;
+    if ground_height(cell) < sea_level {
+        occlusion = 1.0;
     }
 
+    var lightness = 1.0;
+    switch hillshade {
+        case HILLSHADE_SUN: {
+            let diffuse = lambert(surface_normal(cell), light_direction(in.uv, height));
+            lightness = AMBIENT * occlusion + (1.0 - AMBIENT) * diffuse * visibility;
+        }
+        case HILLSHADE_MULTIDIRECTIONAL: {
+            lightness = AMBIENT * occlusion + (1.0 - AMBIENT) * multi_directional(surface_normal(cell));
+        }
+        default: {
+            lightness = 1.0 - SHADOW_STRENGTH * (1.0 - visibility);
+        }
+    }
+    var colour = vec4<f32>(lightness, lightness, lightness, 1.0) * COLOUR_MULTIPLIER;
+
+    // Water below sea level is transparent in the colour map, and drawn here instead
+    let map_colour = textureSample(colour_map, colour_map_sampler, in.uv);
+    let water_amount = 1.0 - map_colour.a;
+    var land = vec4<f32>(map_colour.rgb, 1.0) * colour;
+    if water_amount > 0.0 {
+        let sea = water_colour(in.uv, cell, map_colour.rgb, lightness, visibility);
+        land = vec4<f32>(mix(land.rgb, sea, water_amount), 1.0);
+    }
+
+    // A point light is drawn where it hangs over the map
+    let distance = distance(sun_position, in.uv);
+    let sun_radius = 0.1;
+    if light_model == POINT_LIGHT && distance < sun_radius {
+        land = vec4<f32>(1.0, 1.0, distance / sun_radius, 1.0);
+    }
 
     // return quad_colour * textureSample(height_map, height_map_sampler, in.uv) * textureSample(colour_map, colour_map_sampler, in.uv) * COLOUR_MULTIPLIER;
     // return quad_colour * textureSample(height_map, height_map_sampler, in.uv) * COLOUR_MULTIPLIER;
-    return quad_colour * textureSample(colour_map, colour_map_sampler, in.uv) * colour ;
-}
\ No newline at end of file
+    return quad_colour * land;
+}
@@ -0,0 +1,110 @@
+use bevy::math::vec2;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use islands::prelude::*;
+use ndarray::Array2;
+
+const SIZE: usize = 1024;
+
+/// Run a stage over a fresh 1024² map on thread pools of different sizes,
+/// comparing the single-threaded time against all available threads.
+fn bench_stage(criterion: &mut Criterion, name: &str, stage: &dyn TerrainStage) {
+    let input = TerrainPipeline::island().stages.into_iter().take(2).fold(
+        Array2::zeros((SIZE, SIZE)),
+        |mut height_map, stage| {
+            stage.apply(&mut height_map, &mut TerrainLayers::default(), 1);
+            height_map
+        },
+    );
+
+    let mut group = criterion.benchmark_group(name);
+    group.sample_size(10);
+    for threads in thread_counts() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("threads", threads),
+            &threads,
+            |bencher, _| {
+                bencher.iter(|| {
+                    pool.install(|| {
+                        let mut height_map = input.clone();
+                        stage.apply(&mut height_map, &mut TerrainLayers::default(), 1);
+                        height_map
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// A single thread, then every available thread if there are more.
+fn thread_counts() -> Vec<usize> {
+    let available = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    if available > 1 {
+        vec![1, available]
+    } else {
+        vec![1]
+    }
+}
+
+fn noise(criterion: &mut Criterion) {
+    let stage = NoiseStage::new(vec![
+        (vec2(3.0, 3.0), 1.0),
+        (vec2(5.0, 5.0), 0.7),
+        (vec2(7.0, 7.0), 0.5),
+        (vec2(11.0, 11.0), 0.3),
+        (vec2(13.0, 13.0), 0.2),
+    ]);
+    bench_stage(criterion, "noise", &stage);
+}
+
+fn fractal_noise(criterion: &mut Criterion) {
+    let stage = FractalNoiseStage {
+        algorithm: NoiseAlgorithm::Simplex,
+        ..Default::default()
+    };
+    bench_stage(criterion, "fractal_noise", &stage);
+}
+
+fn archipelago(criterion: &mut Criterion) {
+    bench_stage(criterion, "archipelago", &ArchipelagoStage::default());
+}
+
+fn thermal_erosion(criterion: &mut Criterion) {
+    bench_stage(
+        criterion,
+        "thermal_erosion",
+        &ThermalErosionStage::default(),
+    );
+}
+
+fn island(criterion: &mut Criterion) {
+    let pipeline = TerrainPipeline::island();
+    let mut group = criterion.benchmark_group("island");
+    group.sample_size(10);
+    for threads in thread_counts() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("threads", threads),
+            &threads,
+            |bencher, _| bencher.iter(|| pool.install(|| pipeline.generate(SIZE, SIZE, 1))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    noise,
+    fractal_noise,
+    archipelago,
+    thermal_erosion,
+    island
+);
+criterion_main!(benches);
@@ -1,7 +1,25 @@
 use bevy::prelude::*;
+use rand::prelude::*;
 
 #[derive(Event)]
-pub struct RegenerateTerrain;
+pub struct RegenerateTerrain {
+    pub seed: u64,
+}
+
+impl RegenerateTerrain {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Regenerate with a freshly chosen seed.
+    pub fn random() -> Self {
+        Self::new(thread_rng().gen())
+    }
+}
+
+/// Rerun only the stages which depend on the sea level, such as rivers and lakes, after it changes.
+#[derive(Event)]
+pub struct RegenerateWater;
 
 #[derive(Event)]
 pub struct RedrawTerrain;
@@ -1,6 +1,8 @@
 mod components;
 mod events;
 mod materials;
+mod pipeline;
+mod presets;
 mod resources;
 mod settings;
 mod systems;
@@ -10,6 +12,8 @@ pub mod prelude {
     pub use crate::components::*;
     pub use crate::events::*;
     pub use crate::materials::*;
+    pub use crate::pipeline::*;
+    pub use crate::presets::*;
     pub use crate::resources::*;
     pub use crate::settings::*;
     pub use crate::systems::*;
@@ -1,7 +1,11 @@
 use bevy::{
     prelude::*,
-    render::render_resource::{
-        Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    render::{
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::ImageSampler,
     },
     sprite::{Material2dPlugin, MaterialMesh2dBundle},
     window::Cursor,
@@ -30,18 +34,51 @@ fn main() {
                     ..Default::default()
                 }),
             Material2dPlugin::<CustomMaterial>::default(),
+            HeightMapTexturePlugin,
         ))
         .add_systems(Startup, setup)
+        .add_systems(Startup, setup_gpu_generation)
         .add_systems(Update, bevy::window::close_on_esc)
         .insert_resource(Terrain::new())
+        .insert_resource(TerrainPipeline::island())
+        .insert_resource(TerrainGeneration::default())
+        .insert_resource(TerrainPalette::default())
+        .insert_resource(TerrainView::default())
+        .insert_resource(TerrainOverlay::default())
+        .insert_resource(Sun::default())
+        .insert_resource(Hillshade::default())
+        .init_asset::<TerrainPreset>()
+        .init_asset_loader::<TerrainPresetLoader>()
         .add_event::<RegenerateTerrain>()
+        .add_event::<RegenerateWater>()
         .add_event::<RedrawTerrain>()
         .add_systems(Update, input_events)
         // .add_systems(Update, print_mouse_position)
-        .add_systems(Update, update_sun_position)
+        .add_systems(Update, (follow_mouse, sun_input_events))
+        .add_systems(
+            Update,
+            update_lighting_uniforms
+                .after(input_events)
+                .after(follow_mouse)
+                .after(sun_input_events),
+        )
         .add_systems(Update, regenerate_terrain.after(input_events))
-        .add_systems(Update, redraw_colour_map.after(regenerate_terrain))
-        .add_systems(Update, redraw_height_map.after(regenerate_terrain))
+        .add_systems(Update, regenerate_water.after(regenerate_terrain))
+        .add_systems(Update, receive_terrain.after(regenerate_water))
+        .add_systems(Update, redraw_colour_map.after(receive_terrain))
+        .add_systems(Update, redraw_height_map.after(receive_terrain))
+        .add_systems(
+            Update,
+            redraw_shadow_map
+                .after(receive_terrain)
+                .after(follow_mouse)
+                .after(sun_input_events),
+        )
+        .add_systems(Update, display_seed.after(receive_terrain))
+        .add_systems(Update, display_progress.after(receive_terrain))
+        .add_systems(Update, apply_terrain_preset.after(regenerate_terrain))
+        .add_systems(Update, copy_seed)
+        .add_systems(Update, export_coastlines)
         .run();
 }
 
@@ -49,13 +86,19 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<CustomMaterial>>,
-    // asset_server: Res<AssetServer>,
+    asset_server: Res<AssetServer>,
     mut images: ResMut<Assets<Image>>,
     mut events: EventWriter<RegenerateTerrain>,
 ) {
     // Camera
     commands.spawn(Camera2dBundle::default());
 
+    // Generation preset, hot reloaded when changed on disk
+    commands.insert_resource(TerrainPresetHandle(asset_server.load(format!(
+        "presets/{}.preset.ron",
+        argument("--preset").unwrap_or("island".to_string())
+    ))));
+
     // // Load or create the texture
     // let texture_handle = asset_server.load("textures/blank.png");
 
@@ -66,24 +109,27 @@ fn setup(
         depth_or_array_layers: 1,
     };
 
-    // Height map
+    // Height map, as full precision linear heights
     let height_map = Image {
         texture_descriptor: TextureDescriptor {
             label: None,
             size: texture_size,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8UnormSrgb,
+            format: TextureFormat::R32Float,
             mip_level_count: 1,
             sample_count: 1,
             usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::STORAGE_BINDING
                 | TextureUsages::COPY_DST
-                | TextureUsages::RENDER_ATTACHMENT,
+                | TextureUsages::COPY_SRC,
             view_formats: &[],
         },
-        data: vec![255; (MAP_WIDTH * MAP_HEIGHT * 4) as usize],
+        data: vec![0; (MAP_WIDTH * MAP_HEIGHT * 4) as usize],
+        sampler: ImageSampler::nearest(),
         ..Default::default()
     };
     let height_map_handle = images.add(height_map);
+    commands.insert_resource(HeightMapTexture::new(&height_map_handle));
 
     // Colour map
     let colour_map = Image {
@@ -104,6 +150,44 @@ fn setup(
     };
     let colour_map_handle = images.add(colour_map);
 
+    // Shadow map, recomputed when the sun or terrain changes
+    let shadow_map = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        data: vec![255; (SHADOW_MAP_SIZE * SHADOW_MAP_SIZE) as usize],
+        ..Default::default()
+    };
+    let shadow_map_handle = images.add(shadow_map);
+
+    // Ambient occlusion, recomputed with the terrain
+    let ambient_occlusion = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: texture_size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        data: vec![255; (MAP_WIDTH * MAP_HEIGHT) as usize],
+        ..Default::default()
+    };
+    let ambient_occlusion_handle = images.add(ambient_occlusion);
+
     // Rendering quad
     commands.spawn((
         MaterialMesh2dBundle {
@@ -112,6 +196,8 @@ fn setup(
             material: materials.add(CustomMaterial::new(
                 Some(height_map_handle),
                 Some(colour_map_handle),
+                Some(shadow_map_handle),
+                Some(ambient_occlusion_handle),
             )),
             ..Default::default()
         },
@@ -119,5 +205,36 @@ fn setup(
     ));
 
     // Generate terrain
-    events.send(RegenerateTerrain);
+    events.send(
+        match argument("--seed").and_then(|seed| seed.parse().ok()) {
+            Some(seed) => RegenerateTerrain::new(seed),
+            None => RegenerateTerrain::random(),
+        },
+    );
+}
+
+/// Generate terrain with compute shaders where possible, if run with `--gpu`.
+fn setup_gpu_generation(
+    mut commands: Commands,
+    device: Option<Res<RenderDevice>>,
+    queue: Option<Res<RenderQueue>>,
+) {
+    if !std::env::args().any(|arg| arg == "--gpu") {
+        return;
+    }
+    match (device, queue) {
+        (Some(device), Some(queue)) => {
+            commands.insert_resource(GpuTerrainGenerator::new(device.clone(), queue.clone()))
+        }
+        _ => warn!("No render device, generating on the CPU instead"),
+    }
+}
+
+/// Value following `name` on the command line, as in `--seed 42`, if any.
+fn argument(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .cloned()
 }
@@ -5,30 +5,133 @@ use bevy::{
     sprite::Material2d,
 };
 
+use crate::prelude::*;
+
 // Data is passed to the shader.
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct CustomMaterial {
+    /// Position of a point light, as a fraction of the map size.
     #[uniform(0)]
-    pub mouse_position: Vec2,
+    pub sun_position: Vec2,
     #[uniform(1)]
     pub quad_colour: Color,
-    #[texture(2)]
-    #[sampler(3)]
+    /// Linear `R32Float` heights, which cannot be filtered.
+    #[texture(2, sample_type = "float", filterable = false)]
+    #[sampler(3, sampler_type = "non_filtering")]
     pub height_map: Option<Handle<Image>>,
     #[texture(4)]
     #[sampler(5)]
     pub colour_map: Option<Handle<Image>>,
+    /// How much of the sun reaches each point, from 0 to 1.
+    #[texture(6)]
+    #[sampler(7)]
+    pub shadow_map: Option<Handle<Image>>,
+    /// Unit vector towards a directional sun, with x across the map, y down it and z up.
+    #[uniform(8)]
+    pub sun_direction: Vec3,
+    /// Height of a point light, in the same units as the height map.
+    #[uniform(9)]
+    pub sun_height: f32,
+    /// 0 for a directional sun, and 1 for a point light.
+    #[uniform(10)]
+    pub light_model: u32,
+    /// Fraction of the sky visible from each point, from 0 to 1.
+    #[texture(11)]
+    #[sampler(12)]
+    pub ambient_occlusion: Option<Handle<Image>>,
+    /// Height of the height map's top, as a fraction of the map width.
+    #[uniform(13)]
+    pub terrain_relief: f32,
+    /// 0 for no hillshading, 1 to light the relief with the sun, and 2 to light it from several directions.
+    #[uniform(14)]
+    pub hillshade: u32,
+    /// Height below which the terrain is under water, and shaded as a flat surface.
+    #[uniform(15)]
+    pub sea_level: f32,
+    /// Colour of water at `water_depth` and deeper. Shallower water fades to the colour map's water colour.
+    #[uniform(16)]
+    pub water_deep_colour: Color,
+    /// Colour of the sea bed, seen through shallow water.
+    #[uniform(16)]
+    pub water_bed_colour: Color,
+    /// Colour of the foam along the shore, with its alpha as the foam's opacity.
+    #[uniform(16)]
+    pub foam_colour: Color,
+    /// Depth below sea level, in height units, at which water is fully opaque.
+    #[uniform(16)]
+    pub water_depth: f32,
+    /// Number of wave crests across the map.
+    #[uniform(16)]
+    pub wave_scale: f32,
+    /// Radians of wave phase per second.
+    #[uniform(16)]
+    pub wave_speed: f32,
+    /// Steepness of the waves, from 0 for a still surface.
+    #[uniform(16)]
+    pub wave_strength: f32,
+    /// Brightness of the sun's reflections off the waves.
+    #[uniform(16)]
+    pub glint_strength: f32,
+    /// Specular exponent of the reflections, higher for smaller and sharper glints.
+    #[uniform(16)]
+    pub glint_shininess: f32,
+    /// Depth below sea level, in height units, out to which foam forms along the shore.
+    #[uniform(16)]
+    pub foam_depth: f32,
 }
 
 impl CustomMaterial {
-    pub fn new(height_map: Option<Handle<Image>>, colour_map: Option<Handle<Image>>) -> Self {
+    pub fn new(
+        height_map: Option<Handle<Image>>,
+        colour_map: Option<Handle<Image>>,
+        shadow_map: Option<Handle<Image>>,
+        ambient_occlusion: Option<Handle<Image>>,
+    ) -> Self {
         Self {
-            mouse_position: Vec2::new(0.5, 0.5),
+            sun_position: Vec2::new(0.5, 0.5),
             quad_colour: Color::WHITE,
-            height_map: height_map,
-            colour_map: colour_map,
+            height_map,
+            colour_map,
+            shadow_map,
+            sun_direction: Vec3::Z,
+            sun_height: SUN_HEIGHT,
+            light_model: 1,
+            ambient_occlusion,
+            terrain_relief: TERRAIN_RELIEF,
+            hillshade: 1,
+            sea_level: SEA_LEVEL,
+            water_deep_colour: Color::rgb_u8(36, 82, 122),
+            water_bed_colour: Color::rgb_u8(213, 181, 157),
+            foam_colour: Color::rgba(1.0, 1.0, 1.0, 0.8),
+            water_depth: 0.1,
+            wave_scale: 60.0,
+            wave_speed: 1.5,
+            wave_strength: 0.15,
+            glint_strength: 0.6,
+            glint_shininess: 200.0,
+            foam_depth: 0.01,
         }
     }
+
+    /// Shade the terrain's relief in the given way.
+    pub fn set_hillshade(&mut self, hillshade: Hillshade) {
+        self.hillshade = match hillshade {
+            Hillshade::Off => 0,
+            Hillshade::Sun => 1,
+            Hillshade::MultiDirectional => 2,
+        };
+    }
+
+    /// Light the terrain with the given sun.
+    pub fn set_sun(&mut self, sun: &Sun) {
+        self.sun_position = sun.position;
+        self.sun_direction = sun.direction();
+        self.sun_height = sun.height;
+        self.light_model = match sun.model {
+            LightModel::Directional => 0,
+            LightModel::Point => 1,
+        };
+    }
 }
 
 impl Material2d for CustomMaterial {
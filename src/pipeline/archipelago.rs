@@ -0,0 +1,140 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+/// Mask the height map into several islands scattered across the map.
+/// Centres are placed at random, rejecting any too close to an existing island.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ArchipelagoStage {
+    /// Number of islands to place. Fewer may be placed if they cannot all be fitted in with the required spacing.
+    pub islands: usize,
+    /// Smallest distance between island centres, as a fraction of the map width.
+    pub min_spacing: f32,
+    /// Smallest distance between an island centre and the map edge, as a fraction of the map width.
+    pub margin: f32,
+    /// Range island radii are picked from, as fractions of the map width. Radii under one cell are raised to one cell.
+    pub radius: [f32; 2],
+    /// Shapes islands are picked from.
+    pub shapes: Vec<FalloffShape>,
+    /// How overlapping islands are joined.
+    pub union: IslandUnion,
+}
+
+impl Default for ArchipelagoStage {
+    fn default() -> Self {
+        Self {
+            islands: 6,
+            min_spacing: 0.2,
+            margin: 0.15,
+            radius: [0.06, 0.12],
+            shapes: vec![FalloffShape::Gaussian, FalloffShape::Quadratic],
+            union: IslandUnion::Smooth { k: 0.2 },
+        }
+    }
+}
+
+/// How the masks of neighbouring islands are combined.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum IslandUnion {
+    /// Take the larger mask, leaving a crease where islands meet.
+    Max,
+    /// Polynomial smooth maximum, blending islands within `k` of each other into a saddle.
+    /// The blend never adds more than the smaller mask, so open sea far from both islands stays at zero.
+    Smooth { k: f32 },
+}
+
+impl IslandUnion {
+    pub fn combine(&self, a: f32, b: f32) -> f32 {
+        match *self {
+            Self::Max => a.max(b),
+            Self::Smooth { k } if k > 0.0 && (a - b).abs() < k => {
+                let h = (k - (a - b).abs()) / k;
+                a.max(b) + (h * h * k * 0.25).min(a.min(b).max(0.0))
+            }
+            Self::Smooth { .. } => a.max(b),
+        }
+    }
+}
+
+/// A single island placed by the archipelago stage, in cell coordinates.
+#[derive(Debug, Clone)]
+pub struct IslandSite {
+    pub centre: Vec2,
+    pub radius: f32,
+    pub shape: FalloffShape,
+    /// Seed for any random variation in the shape.
+    pub seed: u64,
+}
+
+/// Random number stream reserved for placing islands.
+const RNG_STREAM: u64 = 2;
+
+/// Number of placement attempts allowed per island before giving up.
+const ATTEMPTS_PER_ISLAND: usize = 30;
+
+impl ArchipelagoStage {
+    /// Pick island centres, radii and shapes for a map of the given size.
+    pub fn sites(&self, width: usize, height: usize, seed: u64) -> Vec<IslandSite> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(RNG_STREAM);
+
+        let size = vec2(width as f32, height as f32);
+        let margin = (self.margin * width as f32).min(size.min_element() * 0.5);
+        let min_spacing = self.min_spacing * width as f32;
+        // Islands are masked by distance over radius, so a radius of zero would divide by zero
+        let (min_radius, max_radius) = (
+            (self.radius[0].min(self.radius[1]) * width as f32).max(1.0),
+            (self.radius[0].max(self.radius[1]) * width as f32).max(1.0),
+        );
+
+        let mut sites: Vec<IslandSite> = Vec::new();
+        for _ in 0..self.islands * ATTEMPTS_PER_ISLAND {
+            if sites.len() == self.islands {
+                break;
+            }
+            let centre = vec2(
+                rng.gen_range(margin..=size.x - margin),
+                rng.gen_range(margin..=size.y - margin),
+            );
+            let radius = rng.gen_range(min_radius..=max_radius);
+            let shape = self.shapes.choose(&mut rng).copied().unwrap_or_default();
+            let seed = rng.gen();
+            if sites
+                .iter()
+                .all(|site| site.centre.distance(centre) >= min_spacing)
+            {
+                sites.push(IslandSite {
+                    centre,
+                    radius,
+                    shape,
+                    seed,
+                });
+            }
+        }
+        sites
+    }
+}
+
+impl TerrainStage for ArchipelagoStage {
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, seed: u64) {
+        let (height, width) = height_map.dim();
+        let sites = self.sites(width, height, seed);
+        par_map_indexed(height_map, |(yi, xi), value| {
+            let position = vec2(xi as f32, yi as f32);
+            let mask = sites
+                .iter()
+                .map(|site| {
+                    let offset = (position - site.centre) / site.radius;
+                    site.shape.mask(offset, site.seed)
+                })
+                .reduce(|mask, site_mask| self.union.combine(mask, site_mask))
+                .unwrap_or(0.0);
+            value * mask.min(1.0)
+        });
+    }
+}
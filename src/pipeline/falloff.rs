@@ -0,0 +1,153 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+use crate::prelude::*;
+
+/// Shape of an island, from its centre out to the sea.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum FalloffShape {
+    /// Gaussian, `exp(-0.5 d^2)`, which never quite reaches zero.
+    #[default]
+    Gaussian,
+    /// Inverted parabola, `1 - d^2`, reaching zero at the radius.
+    Quadratic,
+    /// Rounded square, `|x|^n + |y|^n = 1`, becoming squarer as the exponent grows.
+    Squircle { exponent: f32 },
+    /// Gaussian stretched along one axis by `aspect`, then rotated anticlockwise by `rotation` degrees.
+    Ellipse { aspect: f32, rotation: f32 },
+    /// Gaussian whose radius wobbles with the angle around the centre, by up to `amplitude` of the radius.
+    NoisyRadius { amplitude: f32, harmonics: u32 },
+    /// Atoll: a ring of land at `radius`, `thickness` wide, around a central lagoon. Both are fractions of the island radius.
+    Ring { radius: f32, thickness: f32 },
+    /// Disc with a smaller disc of `inner_radius` bitten out of one side, offset from the centre by `shift`,
+    /// in the direction given by `rotation` degrees anticlockwise. Both lengths are fractions of the island radius.
+    Crescent {
+        shift: f32,
+        inner_radius: f32,
+        rotation: f32,
+    },
+}
+
+impl FalloffShape {
+    /// Scale applied to the height at an offset from the centre, in units of the island radius.
+    /// The seed only affects shapes with random variation.
+    pub fn mask(&self, offset: Vec2, seed: u64) -> f32 {
+        match *self {
+            Self::Gaussian => gaussian(offset.length()),
+            Self::Quadratic => quadratic(offset.length()),
+            Self::Squircle { exponent } => {
+                let exponent = exponent.max(f32::EPSILON);
+                let distance = portable::powf(
+                    portable::powf(offset.x.abs(), exponent)
+                        + portable::powf(offset.y.abs(), exponent),
+                    1.0 / exponent,
+                );
+                quadratic(distance)
+            }
+            Self::Ellipse { aspect, rotation } => {
+                let offset = portable::from_angle(-rotation.to_radians()).rotate(offset);
+                gaussian((offset / vec2(aspect.max(f32::EPSILON), 1.0)).length())
+            }
+            Self::NoisyRadius {
+                amplitude,
+                harmonics,
+            } => {
+                let angle = portable::atan2(offset.y, offset.x);
+                let mut wobble = 0.0;
+                let mut total = 0.0;
+                for harmonic in 1..=harmonics {
+                    let weight = 1.0 / harmonic as f32;
+                    wobble += weight
+                        * portable::sin(harmonic as f32 * angle + harmonic_phase(seed, harmonic));
+                    total += weight;
+                }
+                if total > 0.0 {
+                    wobble /= total;
+                }
+                let radius = (1.0 + amplitude * wobble).max(f32::EPSILON);
+                gaussian(offset.length() / radius)
+            }
+            Self::Ring { radius, thickness } => {
+                let thickness = thickness.max(f32::EPSILON);
+                gaussian((offset.length() - radius) / thickness)
+            }
+            Self::Crescent {
+                shift,
+                inner_radius,
+                rotation,
+            } => {
+                // Zero within the inner circle, rising smoothly back to the disc outside it
+                let bite_centre = portable::from_angle(rotation.to_radians()) * shift;
+                let bite = quadratic(
+                    inner_radius.max(f32::EPSILON)
+                        / (offset - bite_centre).length().max(f32::EPSILON),
+                );
+                quadratic(offset.length()) * bite
+            }
+        }
+    }
+}
+
+fn gaussian(distance: f32) -> f32 {
+    portable::exp(-0.5 * distance * distance)
+}
+
+fn quadratic(distance: f32) -> f32 {
+    (1.0 - distance * distance).max(0.0)
+}
+
+/// Pseudo-random phase in [0, TAU) for one harmonic of a noisy radius, from a SplitMix64 hash.
+pub(crate) fn harmonic_phase(seed: u64, harmonic: u32) -> f32 {
+    let mut z = seed.wrapping_add((harmonic as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32 * TAU
+}
+
+/// Multiply the height map by a single island mask, sinking the map edges into the sea.
+pub struct FalloffStage {
+    /// Centre of the island, as a fraction of the map size.
+    pub centre: Vec2,
+    /// Size of the island, as a fraction of the map width.
+    /// For the Gaussian shape this is the standard deviation.
+    pub radius: f32,
+    pub shape: FalloffShape,
+}
+
+impl FalloffStage {
+    pub fn new(centre: Vec2, radius: f32) -> Self {
+        Self {
+            centre,
+            radius,
+            shape: FalloffShape::Gaussian,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: FalloffShape) -> Self {
+        self.shape = shape;
+        self
+    }
+}
+
+impl TerrainStage for FalloffStage {
+    fn gpu_stage(&self) -> Option<GpuStage> {
+        Some(GpuStage::Falloff {
+            centre: self.centre,
+            radius: self.radius,
+            shape: self.shape,
+        })
+    }
+
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, seed: u64) {
+        let (height, width) = height_map.dim();
+        let centre = self.centre * vec2(width as f32, height as f32);
+        let radius = width as f32 * self.radius;
+        par_map_indexed(height_map, |(yi, xi), value| {
+            let position = vec2(xi as f32, yi as f32);
+            value * self.shape.mask((position - centre) / radius, seed)
+        });
+    }
+}
@@ -0,0 +1,49 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+/// Overwrite the height map with fractal noise, describing the octaves by a few parameters
+/// rather than listing every layer as `NoiseStage` does.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FractalNoiseStage {
+    pub algorithm: NoiseAlgorithm,
+    /// Noise cells across the map in the first octave.
+    pub frequency: f32,
+    pub tiling: NoiseTiling,
+    pub fractal: FractalSettings,
+    pub warp: Option<WarpSettings>,
+}
+
+impl Default for FractalNoiseStage {
+    fn default() -> Self {
+        Self {
+            algorithm: NoiseAlgorithm::Perlin,
+            frequency: 3.0,
+            tiling: NoiseTiling::default(),
+            fractal: FractalSettings::default(),
+            warp: None,
+        }
+    }
+}
+
+impl TerrainStage for FractalNoiseStage {
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, seed: u64) {
+        let base = self
+            .algorithm
+            .layered(seed, &[(Vec2::splat(self.frequency), 1.0)], self.tiling);
+        let mut noise: Box<dyn NoiseSource> = Box::new(Fractal::new(base, self.fractal));
+        if let Some(warp) = &self.warp {
+            noise = warp.warp(noise, seed, self.tiling);
+        }
+
+        let (height, width) = height_map.dim();
+        par_map_indexed(height_map, |(yi, xi), _| {
+            let x = xi as f32 / width as f32;
+            let y = yi as f32 / height as f32;
+            noise.sample(vec2(x, y))
+        });
+    }
+}
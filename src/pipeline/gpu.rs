@@ -0,0 +1,523 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_resource::{
+            binding_types::{
+                storage_buffer_read_only_sized, storage_buffer_sized, texture_storage_2d,
+                uniform_buffer,
+            },
+            encase::UniformBuffer,
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, Buffer,
+            BufferAsyncError, BufferDescriptor, BufferInitDescriptor, BufferUsages, CommandEncoder,
+            CommandEncoderDescriptor, ComputePassDescriptor, ComputePipeline, Extent3d,
+            ImageCopyBuffer, ImageDataLayout, Maintain, MapMode, PipelineLayoutDescriptor,
+            RawComputePipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+            StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages, TextureViewDescriptor,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        Render, RenderApp, RenderSet,
+    },
+};
+use ndarray::Array2;
+use std::sync::{mpsc, Arc, Mutex};
+use thiserror::Error;
+
+use crate::prelude::*;
+
+/// A stage which `GpuTerrainGenerator` can run in a compute shader, as returned by `TerrainStage::gpu_stage`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpuStage {
+    /// Layered Perlin noise with the given frequencies and weights, tiling across the map, as in `NoiseStage`.
+    Noise(Vec<(Vec2, f32)>),
+    Normalise,
+    Falloff {
+        centre: Vec2,
+        radius: f32,
+        shape: FalloffShape,
+    },
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum GpuGenerationError {
+    #[error("Could not read the height map back from the GPU: {0}")]
+    Readback(#[from] BufferAsyncError),
+}
+
+use parameters::GpuParameters;
+
+mod parameters {
+    // The derive's generated layout checks are reported as dead code by newer compilers
+    #![allow(dead_code)]
+
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Uniforms shared by every entry point of the generation shader.
+    #[derive(ShaderType)]
+    pub struct GpuParameters {
+        pub size: UVec2,
+        /// Number of noise layers, or of noisy radius harmonics.
+        pub count: u32,
+        pub shape: u32,
+        /// Falloff centre and radius, in cells.
+        pub centre: Vec2,
+        pub radius: f32,
+        /// Cosine and sine of the falloff rotation.
+        pub rotation: Vec2,
+        pub shape_parameters: Vec2,
+    }
+}
+
+/// Generates height maps with compute shaders, for the leading stages of a pipeline which have GPU equivalents.
+/// Results match the CPU stages to within floating point error, not bit for bit.
+#[derive(Resource, Clone)]
+pub struct GpuTerrainGenerator {
+    device: RenderDevice,
+    queue: RenderQueue,
+    layout: BindGroupLayout,
+    noise: ComputePipeline,
+    falloff: ComputePipeline,
+    measure: ComputePipeline,
+    normalise: ComputePipeline,
+    target_layout: BindGroupLayout,
+    store: ComputePipeline,
+}
+
+impl GpuTerrainGenerator {
+    const WORKGROUP_SIZE: u32 = 8;
+
+    pub fn new(device: RenderDevice, queue: RenderQueue) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("terrain_generation_shader"),
+            source: ShaderSource::Wgsl(include_str!("../../assets/shaders/generation.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(
+            "terrain_generation_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer::<GpuParameters>(false),
+                    storage_buffer_sized(false, None),
+                    storage_buffer_read_only_sized(false, None),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("terrain_generation_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let target_layout = device.create_bind_group_layout(
+            "terrain_generation_target_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::COMPUTE,
+                texture_storage_2d(TextureFormat::R32Float, StorageTextureAccess::WriteOnly),
+            ),
+        );
+        let store_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("terrain_generation_store_layout"),
+            bind_group_layouts: &[&layout, &target_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point, layout| {
+            device.create_compute_pipeline(&RawComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+
+        Self {
+            noise: pipeline("noise", &pipeline_layout),
+            falloff: pipeline("falloff", &pipeline_layout),
+            measure: pipeline("measure", &pipeline_layout),
+            normalise: pipeline("normalise", &pipeline_layout),
+            store: pipeline("store", &store_layout),
+            device,
+            queue,
+            layout,
+            target_layout,
+        }
+    }
+
+    /// Generator on a device of its own, outside of any app, such as a software adapter.
+    /// The backends can be chosen with the `WGPU_BACKEND` environment variable.
+    pub fn headless() -> Option<Self> {
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all());
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..default()
+        });
+        let adapter = bevy::tasks::block_on(instance.request_adapter(&default()))?;
+        let (device, queue) = bevy::tasks::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("terrain_generation_device"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+            },
+            None,
+        ))
+        .ok()?;
+        Some(Self::new(device.into(), RenderQueue(Arc::new(queue))))
+    }
+
+    /// `R32Float` texture which `apply` can write a height map of the given size into.
+    pub fn height_map_texture(&self, width: usize, height: usize) -> Texture {
+        self.device.create_texture(&TextureDescriptor {
+            label: Some("terrain_generation_height_map"),
+            size: Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::STORAGE_BINDING
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Run the leading stages of the pipeline which have GPU equivalents over the height map,
+    /// returning how many of them ran. The rest are left for the CPU.
+    ///
+    /// Given a target, an `R32Float` storage texture the size of the height map such as the one
+    /// the terrain is drawn from, the heights are written straight into it, and only read back from
+    /// there to fill in the height map for the stages which follow.
+    pub fn apply(
+        &self,
+        pipeline: &TerrainPipeline,
+        height_map: &mut Array2<f32>,
+        seed: u64,
+        target: Option<&Texture>,
+    ) -> Result<usize, GpuGenerationError> {
+        let stages: Vec<GpuStage> = pipeline
+            .stages
+            .iter()
+            .map_while(|stage| stage.gpu_stage())
+            .collect();
+        if stages.is_empty() {
+            return Ok(0);
+        }
+
+        let (height, width) = height_map.dim();
+        let size = UVec2::new(width as u32, height as u32);
+        let contents: Vec<u8> = height_map.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let heights = self.device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("terrain_generation_heights"),
+            contents: &contents,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        });
+        let range = self.device.create_buffer(&BufferDescriptor {
+            label: Some("terrain_generation_range"),
+            size: 8,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Rows copied out of a texture are padded to the copy alignment
+        let row_bytes = 4 * width as u32;
+        let padded_row_bytes = match target {
+            Some(_) => row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+            None => row_bytes,
+        };
+        let readback = self.device.create_buffer(&BufferDescriptor {
+            label: Some("terrain_generation_readback"),
+            size: (padded_row_bytes * size.y) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("terrain_generation_encoder"),
+            });
+        for stage in &stages {
+            let (parameters, data) = Self::stage_inputs(stage, size, seed);
+            let run = |encoder: &mut CommandEncoder, pipeline| {
+                self.dispatch(
+                    encoder,
+                    pipeline,
+                    &parameters,
+                    &data,
+                    &heights,
+                    &range,
+                    None,
+                )
+            };
+            match stage {
+                GpuStage::Noise(_) => run(&mut encoder, &self.noise),
+                GpuStage::Falloff { .. } => run(&mut encoder, &self.falloff),
+                GpuStage::Normalise => {
+                    encoder.clear_buffer(&range, 0, None);
+                    run(&mut encoder, &self.measure);
+                    run(&mut encoder, &self.normalise);
+                }
+            }
+        }
+        match target {
+            Some(texture) => {
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                let target = self.device.create_bind_group(
+                    "terrain_generation_target",
+                    &self.target_layout,
+                    &BindGroupEntries::single(&view),
+                );
+                let (parameters, data) = Self::stage_inputs(&GpuStage::Normalise, size, seed);
+                self.dispatch(
+                    &mut encoder,
+                    &self.store,
+                    &parameters,
+                    &data,
+                    &heights,
+                    &range,
+                    Some(&target),
+                );
+                encoder.copy_texture_to_buffer(
+                    texture.as_image_copy(),
+                    ImageCopyBuffer {
+                        buffer: &readback,
+                        layout: ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(padded_row_bytes),
+                            rows_per_image: None,
+                        },
+                    },
+                    Extent3d {
+                        width: size.x,
+                        height: size.y,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+            None => encoder.copy_buffer_to_buffer(&heights, 0, &readback, 0, contents.len() as u64),
+        }
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(Maintain::Wait);
+        receiver
+            .recv()
+            .expect("Readback finishes once the device is idle")?;
+        {
+            let mapped = slice.get_mapped_range();
+            let rows = mapped
+                .chunks_exact(padded_row_bytes as usize)
+                .map(|row| &row[..row_bytes as usize]);
+            for (mut heights, row) in height_map.rows_mut().into_iter().zip(rows) {
+                for (value, bytes) in heights.iter_mut().zip(row.chunks_exact(4)) {
+                    *value = f32::from_le_bytes(bytes.try_into().unwrap());
+                }
+            }
+        }
+        readback.unmap();
+
+        Ok(stages.len())
+    }
+
+    /// Generate a new height map of the given size, running what it can on the GPU and the rest on the CPU.
+    pub fn generate(
+        &self,
+        pipeline: &TerrainPipeline,
+        width: usize,
+        height: usize,
+        seed: u64,
+    ) -> Result<(Array2<f32>, TerrainLayers), GpuGenerationError> {
+        let mut height_map = Array2::zeros((height, width));
+        let mut layers = TerrainLayers::default();
+        let gpu_stages = self.apply(pipeline, &mut height_map, seed, None)?;
+        for stage in &pipeline.stages[gpu_stages..] {
+            stage.apply(&mut height_map, &mut layers, seed);
+        }
+        Ok((height_map, layers))
+    }
+
+    /// Uniforms and data buffer contents for a stage.
+    fn stage_inputs(stage: &GpuStage, size: UVec2, seed: u64) -> (GpuParameters, Vec<f32>) {
+        let mut parameters = GpuParameters {
+            size,
+            count: 0,
+            shape: 0,
+            centre: Vec2::ZERO,
+            radius: 1.0,
+            rotation: Vec2::X,
+            shape_parameters: Vec2::ZERO,
+        };
+        let mut data = Vec::new();
+        match stage {
+            GpuStage::Noise(layers) => {
+                // Same gradients as the CPU stage, laid out after a header per layer
+                let noise = PerlinNoise::periodic(seed, layers.clone(), Vec2::ONE);
+                parameters.count = noise.layers.len() as u32;
+                let mut offset = 4 * noise.layers.len();
+                let mut gradients = Vec::new();
+                for layer in &noise.layers {
+                    let PerlinLattice::Periodic(vectors) = &layer.lattice else {
+                        unreachable!("Noise stages use periodic lattices");
+                    };
+                    data.extend([
+                        vectors.ncols() as f32,
+                        vectors.nrows() as f32,
+                        layer.weight,
+                        offset as f32,
+                    ]);
+                    gradients.extend(vectors.iter().flat_map(|gradient| gradient.to_array()));
+                    offset += 2 * vectors.len();
+                }
+                data.extend(gradients);
+            }
+            GpuStage::Normalise => {}
+            GpuStage::Falloff {
+                centre,
+                radius,
+                shape,
+            } => {
+                let map_size = size.as_vec2();
+                parameters.centre = *centre * map_size;
+                parameters.radius = map_size.x * radius;
+                let (shape, rotation, shape_parameters) = match *shape {
+                    FalloffShape::Gaussian => (0, 0.0, Vec2::ZERO),
+                    FalloffShape::Quadratic => (1, 0.0, Vec2::ZERO),
+                    FalloffShape::Squircle { exponent } => (2, 0.0, Vec2::new(exponent, 0.0)),
+                    FalloffShape::Ellipse { aspect, rotation } => {
+                        (3, -rotation, Vec2::new(aspect, 0.0))
+                    }
+                    FalloffShape::NoisyRadius {
+                        amplitude,
+                        harmonics,
+                    } => {
+                        parameters.count = harmonics;
+                        data.extend((1..=harmonics).map(|harmonic| harmonic_phase(seed, harmonic)));
+                        (4, 0.0, Vec2::new(amplitude, 0.0))
+                    }
+                    FalloffShape::Ring { radius, thickness } => {
+                        (5, 0.0, Vec2::new(radius, thickness))
+                    }
+                    FalloffShape::Crescent {
+                        shift,
+                        inner_radius,
+                        rotation,
+                    } => (6, rotation, Vec2::new(shift, inner_radius)),
+                };
+                parameters.shape = shape;
+                parameters.rotation = portable::from_angle(rotation.to_radians());
+                parameters.shape_parameters = shape_parameters;
+            }
+        }
+        // Bindings cannot be empty
+        if data.is_empty() {
+            data.push(0.0);
+        }
+        (parameters, data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn dispatch(
+        &self,
+        encoder: &mut CommandEncoder,
+        pipeline: &ComputePipeline,
+        parameters: &GpuParameters,
+        data: &[f32],
+        heights: &Buffer,
+        range: &Buffer,
+        target: Option<&BindGroup>,
+    ) {
+        let mut uniform = UniformBuffer::new(Vec::new());
+        uniform.write(parameters).unwrap();
+        let uniform = self.device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("terrain_generation_parameters"),
+            contents: uniform.as_ref(),
+            usage: BufferUsages::UNIFORM,
+        });
+        let data: Vec<u8> = data.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let data = self.device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("terrain_generation_data"),
+            contents: &data,
+            usage: BufferUsages::STORAGE,
+        });
+        let bind_group = self.device.create_bind_group(
+            "terrain_generation_bind_group",
+            &self.layout,
+            &BindGroupEntries::sequential((
+                uniform.as_entire_binding(),
+                heights.as_entire_binding(),
+                data.as_entire_binding(),
+                range.as_entire_binding(),
+            )),
+        );
+
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("terrain_generation_pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        if let Some(target) = target {
+            pass.set_bind_group(1, target, &[]);
+        }
+        pass.dispatch_workgroups(
+            parameters.size.x.div_ceil(Self::WORKGROUP_SIZE),
+            parameters.size.y.div_ceil(Self::WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
+
+/// Height map image the terrain is drawn from, and its texture on the GPU for `GpuTerrainGenerator` to write into.
+/// The texture is shared from the render world by `share_height_map_texture`, and replaced whenever the image is.
+#[derive(Resource, ExtractResource, Clone)]
+pub struct HeightMapTexture {
+    pub image: AssetId<Image>,
+    texture: Arc<Mutex<Option<Texture>>>,
+}
+
+impl HeightMapTexture {
+    pub fn new(image: impl Into<AssetId<Image>>) -> Self {
+        Self {
+            image: image.into(),
+            texture: default(),
+        }
+    }
+
+    /// Current texture of the image, once it has been uploaded.
+    pub fn texture(&self) -> Option<Texture> {
+        self.texture.lock().unwrap().clone()
+    }
+}
+
+/// Shares the texture of the `HeightMapTexture` image, once that resource is inserted.
+pub struct HeightMapTexturePlugin;
+
+impl Plugin for HeightMapTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractResourcePlugin::<HeightMapTexture>::default());
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_systems(
+                Render,
+                share_height_map_texture
+                    .in_set(RenderSet::PrepareResources)
+                    .run_if(resource_exists::<HeightMapTexture>),
+            );
+        }
+    }
+}
+
+/// Share the height map image's current texture with the main world, in the render world once assets are prepared.
+fn share_height_map_texture(height_map: Res<HeightMapTexture>, images: Res<RenderAssets<Image>>) {
+    let texture = images
+        .get(height_map.image)
+        .map(|image| image.texture.clone());
+    *height_map.texture.lock().unwrap() = texture;
+}
@@ -0,0 +1,215 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+/// Particle based hydraulic erosion.
+/// Droplets of water roll downhill, picking up sediment when they speed up and dropping it when they slow down,
+/// carving valleys and drainage channels into the height map.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HydraulicErosionStage {
+    /// Number of droplets to simulate.
+    pub iterations: usize,
+    /// Maximum number of steps each droplet takes before it is discarded.
+    pub max_lifetime: usize,
+    /// How strongly a droplet keeps its previous direction, from 0 (follows the slope) to 1 (never turns).
+    pub inertia: f32,
+    /// Multiplier for how much sediment a droplet can carry.
+    pub capacity: f32,
+    /// Minimum carrying capacity, which stops droplets on flat ground from depositing everything at once.
+    pub min_capacity: f32,
+    /// Fraction of excess sediment dropped each step.
+    pub deposition: f32,
+    /// Fraction of spare capacity filled by eroding the ground each step.
+    pub erosion: f32,
+    /// Fraction of water lost each step.
+    pub evaporation: f32,
+    /// Acceleration of droplets down slopes.
+    pub gravity: f32,
+    /// Radius, in cells, over which erosion is spread.
+    pub radius: usize,
+}
+
+impl Default for HydraulicErosionStage {
+    fn default() -> Self {
+        Self {
+            iterations: 50_000,
+            max_lifetime: 30,
+            inertia: 0.05,
+            capacity: 20.0,
+            min_capacity: 0.01,
+            deposition: 0.3,
+            erosion: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+            radius: 3,
+        }
+    }
+}
+
+/// Random number stream reserved for hydraulic erosion, so droplets are independent of the noise.
+const RNG_STREAM: u64 = 1;
+
+/// Number of droplets between progress reports.
+const PROGRESS_INTERVAL: usize = 1024;
+
+impl TerrainStage for HydraulicErosionStage {
+    fn apply(&self, height_map: &mut Array2<f32>, layers: &mut TerrainLayers, seed: u64) {
+        self.apply_with_progress(height_map, layers, seed, &GenerationProgress::default());
+    }
+
+    fn apply_with_progress(
+        &self,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        seed: u64,
+        progress: &GenerationProgress,
+    ) {
+        let (height, width) = height_map.dim();
+        if width < 2 || height < 2 {
+            return;
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(RNG_STREAM);
+        let brush = self.brush();
+        let erosion_map = layers.erosion_mut(height_map.dim());
+
+        for iteration in 0..self.iterations {
+            if iteration % PROGRESS_INTERVAL == 0 && !progress.report(iteration, self.iterations) {
+                return;
+            }
+            let mut position = vec2(
+                rng.gen_range(0.0..(width - 1) as f32),
+                rng.gen_range(0.0..(height - 1) as f32),
+            );
+            let mut direction = Vec2::ZERO;
+            let mut speed = 1.0;
+            let mut water = 1.0;
+            let mut sediment = 0.0;
+
+            for _ in 0..self.max_lifetime {
+                let cell = (position.y as usize, position.x as usize);
+                let offset = position - position.floor();
+                let (current_height, gradient) = height_and_gradient(height_map, position);
+
+                // Turn towards the downhill direction
+                direction = direction * self.inertia - gradient * (1.0 - self.inertia);
+                if direction.length_squared() == 0.0 {
+                    break;
+                }
+                direction = direction.normalize();
+                position += direction;
+
+                if position.x < 0.0
+                    || position.y < 0.0
+                    || position.x >= (width - 1) as f32
+                    || position.y >= (height - 1) as f32
+                {
+                    break;
+                }
+
+                let delta_height = height_and_gradient(height_map, position).0 - current_height;
+                let capacity =
+                    (-delta_height * speed * water * self.capacity).max(self.min_capacity);
+
+                if sediment > capacity || delta_height > 0.0 {
+                    // Fill the pit being climbed out of, or drop the excess sediment
+                    let amount = if delta_height > 0.0 {
+                        delta_height.min(sediment)
+                    } else {
+                        (sediment - capacity) * self.deposition
+                    };
+                    sediment -= amount;
+                    deposit(height_map, erosion_map, cell, offset, amount);
+                } else {
+                    let amount = ((capacity - sediment) * self.erosion).min(-delta_height);
+                    for &((dy, dx), weight) in &brush {
+                        let y = cell.0 as isize + dy;
+                        let x = cell.1 as isize + dx;
+                        if y < 0 || x < 0 || y >= height as isize || x >= width as isize {
+                            continue;
+                        }
+                        let index = (y as usize, x as usize);
+                        let eroded = (amount * weight).min(height_map[index]);
+                        height_map[index] -= eroded;
+                        erosion_map[index] -= eroded;
+                        sediment += eroded;
+                    }
+                }
+
+                speed = (speed * speed - delta_height * self.gravity)
+                    .max(0.0)
+                    .sqrt();
+                water *= 1.0 - self.evaporation;
+            }
+        }
+    }
+}
+
+impl HydraulicErosionStage {
+    /// Offsets and weights of the cells eroded around a droplet, with weights falling off linearly and summing to one.
+    fn brush(&self) -> Vec<((isize, isize), f32)> {
+        let radius = self.radius.max(1) as isize;
+        let mut brush = Vec::new();
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if distance < radius as f32 {
+                    brush.push(((dy, dx), radius as f32 - distance));
+                }
+            }
+        }
+        let total: f32 = brush.iter().map(|(_, weight)| weight).sum();
+        for (_, weight) in &mut brush {
+            *weight /= total;
+        }
+        brush
+    }
+}
+
+/// Bilinearly interpolated height, and its gradient, at a position in cell coordinates.
+fn height_and_gradient(height_map: &Array2<f32>, position: Vec2) -> (f32, Vec2) {
+    let (yi, xi) = (position.y as usize, position.x as usize);
+    let (u, v) = (position.x - xi as f32, position.y - yi as f32);
+
+    let top_left = height_map[(yi, xi)];
+    let top_right = height_map[(yi, xi + 1)];
+    let bottom_left = height_map[(yi + 1, xi)];
+    let bottom_right = height_map[(yi + 1, xi + 1)];
+
+    let gradient = vec2(
+        (top_right - top_left) * (1.0 - v) + (bottom_right - bottom_left) * v,
+        (bottom_left - top_left) * (1.0 - u) + (bottom_right - top_right) * u,
+    );
+    let height = top_left * (1.0 - u) * (1.0 - v)
+        + top_right * u * (1.0 - v)
+        + bottom_left * (1.0 - u) * v
+        + bottom_right * u * v;
+
+    (height, gradient)
+}
+
+/// Spread sediment over the four cells surrounding a droplet.
+fn deposit(
+    height_map: &mut Array2<f32>,
+    erosion_map: &mut Array2<f32>,
+    (yi, xi): (usize, usize),
+    offset: Vec2,
+    amount: f32,
+) {
+    let (u, v) = (offset.x, offset.y);
+    for (index, weight) in [
+        ((yi, xi), (1.0 - u) * (1.0 - v)),
+        ((yi, xi + 1), u * (1.0 - v)),
+        ((yi + 1, xi), (1.0 - u) * v),
+        ((yi + 1, xi + 1), u * v),
+    ] {
+        height_map[index] += amount * weight;
+        erosion_map[index] += amount * weight;
+    }
+}
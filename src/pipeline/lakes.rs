@@ -0,0 +1,125 @@
+use ndarray::Array2;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+/// Fill depressions above sea level with lakes.
+/// Each basin found by the priority-flood fills with water up to its spill height, the height at which it would overflow.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LakeStage {
+    /// Height of the sea; depressions filling below it are part of the sea.
+    /// Set from the terrain when generating, through `TerrainPipeline::with_sea_level`.
+    #[serde(skip)]
+    pub sea_level: f32,
+    /// Depth of the deepest point a depression needs to become a lake.
+    pub min_depth: f32,
+    /// Number of cells a depression needs to cover to become a lake.
+    pub min_area: usize,
+}
+
+impl Default for LakeStage {
+    fn default() -> Self {
+        Self {
+            sea_level: SEA_LEVEL,
+            min_depth: 0.001,
+            min_area: 20,
+        }
+    }
+}
+
+/// A single body of inland water.
+#[derive(Debug, Clone)]
+pub struct Lake {
+    /// Identifier used in the lake ID layer, starting from 1.
+    pub id: u32,
+    /// Height of the flat water surface.
+    pub spill_height: f32,
+    /// Number of cells covered.
+    pub area: usize,
+    /// Depth of water at the deepest point.
+    pub max_depth: f32,
+}
+
+impl TerrainStage for LakeStage {
+    fn with_sea_level(&self, sea_level: f32) -> Option<Arc<dyn TerrainStage>> {
+        Some(Arc::new(Self {
+            sea_level,
+            ..self.clone()
+        }))
+    }
+
+    fn apply(&self, height_map: &mut Array2<f32>, layers: &mut TerrainLayers, seed: u64) {
+        self.apply_with_progress(height_map, layers, seed, &GenerationProgress::default());
+    }
+
+    /// Reports progress while filling depressions, which takes most of the time.
+    fn apply_with_progress(
+        &self,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        _seed: u64,
+        progress: &GenerationProgress,
+    ) {
+        let (height, width) = height_map.dim();
+        let Some(drainage) = Drainage::with_progress(height_map, progress) else {
+            return;
+        };
+        let filled = drainage.filled;
+        let is_flooded = |index: (usize, usize)| {
+            filled[index] > height_map[index] && filled[index] > self.sea_level
+        };
+
+        let mut lake_ids = Array2::zeros(height_map.dim());
+        let mut water_surface = Array2::zeros(height_map.dim());
+        let mut lakes = Vec::new();
+        let mut visited = Array2::from_elem(height_map.dim(), false);
+        let mut stack = Vec::new();
+
+        for start in (0..height).flat_map(|yi| (0..width).map(move |xi| (yi, xi))) {
+            if visited[start] || !is_flooded(start) {
+                continue;
+            }
+
+            // Collect every connected flooded cell sharing this water surface
+            let spill_height = filled[start];
+            let mut cells = Vec::new();
+            let mut max_depth: f32 = 0.0;
+            visited[start] = true;
+            stack.push(start);
+            while let Some(index) = stack.pop() {
+                cells.push(index);
+                max_depth = max_depth.max(spill_height - height_map[index]);
+                for neighbour in neighbours(index, (height, width)) {
+                    if !visited[neighbour]
+                        && is_flooded(neighbour)
+                        && filled[neighbour] == spill_height
+                    {
+                        visited[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+
+            if cells.len() < self.min_area || max_depth < self.min_depth {
+                continue;
+            }
+            let id = lakes.len() as u32 + 1;
+            for &index in &cells {
+                lake_ids[index] = id;
+                water_surface[index] = spill_height;
+            }
+            lakes.push(Lake {
+                id,
+                spill_height,
+                area: cells.len(),
+                max_depth,
+            });
+        }
+
+        layers.lake_ids = Some(lake_ids);
+        layers.water_surface = Some(water_surface);
+        layers.lakes = lakes;
+    }
+}
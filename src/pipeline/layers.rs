@@ -0,0 +1,31 @@
+use ndarray::Array2;
+
+use crate::prelude::*;
+
+/// Per-cell data produced by stages alongside the height map.
+#[derive(Default, Clone)]
+pub struct TerrainLayers {
+    /// Net material moved by erosion: positive where deposited, negative where eroded.
+    pub erosion: Option<Array2<f32>>,
+    /// Water flowing through each river cell, as a number of upstream cells, and zero away from rivers.
+    pub rivers: Option<Array2<f32>>,
+    /// Identifier of the lake covering each cell, and zero away from lakes.
+    pub lake_ids: Option<Array2<u32>>,
+    /// Height of the lake surface covering each cell, and zero away from lakes.
+    pub water_surface: Option<Array2<f32>>,
+    /// Every lake, indexed by its identifier minus one.
+    pub lakes: Vec<Lake>,
+}
+
+impl TerrainLayers {
+    /// Erosion layer matching the height map's shape, created empty if no stage has written to it yet.
+    pub fn erosion_mut(&mut self, dim: (usize, usize)) -> &mut Array2<f32> {
+        self.erosion.get_or_insert_with(|| Array2::zeros(dim))
+    }
+
+    /// Height of the lake surface at a cell, if it is covered by a lake.
+    pub fn lake_surface(&self, index: (usize, usize)) -> Option<f32> {
+        let lake_id = self.lake_ids.as_ref()?[index];
+        (lake_id > 0).then(|| self.lakes[lake_id as usize - 1].spill_height)
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+use ndarray::Array2;
+
+use crate::prelude::*;
+
+/// Multiply the height map by a user-supplied greyscale mask, stretched to cover the whole map.
+/// White keeps the terrain as it is and black sinks it to zero.
+pub struct MaskStage {
+    pub mask: Array2<f32>,
+}
+
+impl MaskStage {
+    pub fn new(mask: Array2<f32>) -> Self {
+        Self { mask }
+    }
+
+    /// Read the mask from the first channel of an image.
+    /// Returns `None` for texture formats other than 8 or 16 bit unsigned integers.
+    pub fn from_image(image: &Image) -> Option<Self> {
+        let width = image.texture_descriptor.size.width as usize;
+        let height = image.texture_descriptor.size.height as usize;
+        let (bytes_per_pixel, wide) = match image.texture_descriptor.format {
+            TextureFormat::R8Unorm => (1, false),
+            TextureFormat::Rg8Unorm => (2, false),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => (4, false),
+            TextureFormat::R16Uint | TextureFormat::R16Unorm => (2, true),
+            TextureFormat::Rgba16Uint | TextureFormat::Rgba16Unorm => (8, true),
+            _ => return None,
+        };
+        if width == 0 || height == 0 || image.data.len() < width * height * bytes_per_pixel {
+            return None;
+        }
+
+        let mask = Array2::from_shape_fn((height, width), |(yi, xi)| {
+            let index = (yi * width + xi) * bytes_per_pixel;
+            if wide {
+                u16::from_le_bytes([image.data[index], image.data[index + 1]]) as f32
+                    / u16::MAX as f32
+            } else {
+                image.data[index] as f32 / u8::MAX as f32
+            }
+        });
+        Some(Self::new(mask))
+    }
+
+    /// Bilinearly interpolated mask value at a position in mask cell coordinates.
+    fn sample(&self, position: Vec2) -> f32 {
+        let (height, width) = self.mask.dim();
+        let position = position.clamp(
+            Vec2::ZERO,
+            Vec2::new(width as f32 - 1.0, height as f32 - 1.0),
+        );
+        let (x0, y0) = (position.x as usize, position.y as usize);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (tx, ty) = (position.x - x0 as f32, position.y - y0 as f32);
+        let top = self.mask[(y0, x0)] * (1.0 - tx) + self.mask[(y0, x1)] * tx;
+        let bottom = self.mask[(y1, x0)] * (1.0 - tx) + self.mask[(y1, x1)] * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+impl TerrainStage for MaskStage {
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, _seed: u64) {
+        let (height, width) = height_map.dim();
+        let (mask_height, mask_width) = self.mask.dim();
+        if mask_height == 0 || mask_width == 0 {
+            return;
+        }
+
+        // Match cell centres so the mask covers the map exactly, whatever its resolution
+        let scale = Vec2::new(
+            mask_width as f32 / width as f32,
+            mask_height as f32 / height as f32,
+        );
+        par_map_indexed(height_map, |(yi, xi), value| {
+            let position = (Vec2::new(xi as f32, yi as f32) + 0.5) * scale - 0.5;
+            value * self.sample(position)
+        });
+    }
+}
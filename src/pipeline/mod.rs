@@ -0,0 +1,33 @@
+mod archipelago;
+mod falloff;
+mod fractal_noise;
+mod gpu;
+mod hydraulic_erosion;
+mod lakes;
+mod layers;
+mod mask;
+mod noise;
+mod normalise;
+mod progress;
+mod rivers;
+mod sea_level;
+mod stage;
+mod terrain_pipeline;
+mod thermal_erosion;
+
+pub use archipelago::*;
+pub use falloff::*;
+pub use fractal_noise::*;
+pub use gpu::*;
+pub use hydraulic_erosion::*;
+pub use lakes::*;
+pub use layers::*;
+pub use mask::*;
+pub use noise::*;
+pub use normalise::*;
+pub use progress::*;
+pub use rivers::*;
+pub use sea_level::*;
+pub use stage::*;
+pub use terrain_pipeline::*;
+pub use thermal_erosion::*;
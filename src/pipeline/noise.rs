@@ -0,0 +1,225 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+/// Noise generator used by a `NoiseStage`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoiseAlgorithm {
+    #[default]
+    Perlin,
+    Simplex,
+    OpenSimplex2,
+    Value,
+    /// 4D Perlin noise around a torus, tiling like `Perlin` without lining up with a grid.
+    PerlinTorus,
+    Worley {
+        #[serde(default)]
+        metric: DistanceMetric,
+        #[serde(default)]
+        feature: WorleyFeature,
+    },
+}
+
+/// How noise repeats across the world, with positions measured in maps.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NoiseTiling {
+    /// Repeat every `period` maps along each axis, rounding each frequency to fit a whole number of cells.
+    /// The default period of one map wraps the noise seamlessly at the map edges.
+    Periodic { period: Vec2 },
+    /// Never repeat, so any position, including a negative one, has its own noise.
+    /// `PerlinTorus` and `Worley` always repeat, so they repeat every map instead.
+    Infinite,
+}
+
+impl Default for NoiseTiling {
+    fn default() -> Self {
+        Self::Periodic { period: Vec2::ONE }
+    }
+}
+
+impl NoiseTiling {
+    /// Period of algorithms which always repeat.
+    fn period(&self) -> Vec2 {
+        match *self {
+            Self::Periodic { period } => period.max(Vec2::splat(f32::EPSILON)),
+            Self::Infinite => Vec2::ONE,
+        }
+    }
+}
+
+impl NoiseAlgorithm {
+    /// Layered noise source with the given frequencies, in noise cells per map, and weights.
+    /// `Simplex`, `OpenSimplex2` and `Value` noise do not repeat at any period, so ignore the tiling.
+    pub fn layered(
+        &self,
+        seed: u64,
+        layers: &[(Vec2, f32)],
+        tiling: NoiseTiling,
+    ) -> Box<dyn NoiseSource> {
+        let period = tiling.period();
+        match self {
+            Self::Perlin => match tiling {
+                NoiseTiling::Periodic { .. } => {
+                    Box::new(PerlinNoise::periodic(seed, layers.to_vec(), period))
+                }
+                NoiseTiling::Infinite => Box::new(PerlinNoise::infinite(seed, layers.to_vec())),
+            },
+            Self::Simplex => Box::new(LayeredNoise::new(seed, layers, SimplexNoise::new)),
+            Self::OpenSimplex2 => Box::new(LayeredNoise::new(seed, layers, OpenSimplex2Noise::new)),
+            Self::Value => Box::new(LayeredNoise::new(seed, layers, ValueNoise::new)),
+            Self::PerlinTorus => {
+                // Each layer's frequency sets the size of its own torus, which wraps once per period
+                let layers = layers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (frequency, weight))| {
+                        let torus =
+                            TorusNoise::new(seed.wrapping_add(index as u64), *frequency * period);
+                        let source: Box<dyn NoiseSource> = Box::new(torus);
+                        (source, 1.0 / period, *weight)
+                    })
+                    .collect();
+                Box::new(LayeredNoise { layers })
+            }
+            Self::Worley { metric, feature } => {
+                // Worley grids tile by themselves, so fit a whole number of cells into the period, like Perlin layers
+                let layers = layers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (frequency, weight))| {
+                        let cells = (*frequency * period).round().max(Vec2::ONE);
+                        let worley = WorleyNoise::new(
+                            seed.wrapping_add(index as u64),
+                            (cells.x as usize, cells.y as usize),
+                            *metric,
+                        )
+                        .with_feature(*feature);
+                        let source: Box<dyn NoiseSource> = Box::new(worley);
+                        (source, 1.0 / period, *weight)
+                    })
+                    .collect();
+                Box::new(LayeredNoise { layers })
+            }
+        }
+    }
+}
+
+/// Domain warp bending the noise of a noise stage.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct WarpSettings {
+    /// Generator of the warp field.
+    pub algorithm: NoiseAlgorithm,
+    /// Noise cells across the map in the warp field.
+    pub frequency: f32,
+    /// Octaves of fBm in the warp field.
+    pub octaves: u32,
+    /// Largest offset, as a fraction of the map.
+    pub strength: f32,
+    /// Number of times the warp field warps itself.
+    pub iterations: u32,
+}
+
+impl Default for WarpSettings {
+    fn default() -> Self {
+        Self {
+            algorithm: NoiseAlgorithm::Perlin,
+            frequency: 4.0,
+            octaves: 3,
+            strength: 0.05,
+            iterations: 1,
+        }
+    }
+}
+
+impl WarpSettings {
+    /// Added to the seed of the warp field, so it differs from the noise it warps.
+    const SEED_OFFSET: u64 = 0x5741_5250;
+
+    /// Wrap a noise source, sampled with positions as fractions of the map, in this warp.
+    /// The warp field repeats like the source, so warping does not break its tiling.
+    pub fn warp(
+        &self,
+        source: Box<dyn NoiseSource>,
+        seed: u64,
+        tiling: NoiseTiling,
+    ) -> Box<dyn NoiseSource> {
+        let field = self.algorithm.layered(
+            seed.wrapping_add(Self::SEED_OFFSET),
+            &[(Vec2::splat(self.frequency), 1.0)],
+            tiling,
+        );
+        let field = Fractal::new(
+            field,
+            FractalSettings {
+                octaves: self.octaves,
+                ..default()
+            },
+        );
+        Box::new(
+            DomainWarp::new(source, Box::new(field), self.strength)
+                .with_iterations(self.iterations),
+        )
+    }
+}
+
+/// Overwrite the height map with layered noise.
+pub struct NoiseStage {
+    /// Frequencies, in noise cells across and down the map, and their weights.
+    pub layers: Vec<(Vec2, f32)>,
+    pub algorithm: NoiseAlgorithm,
+    pub tiling: NoiseTiling,
+    pub warp: Option<WarpSettings>,
+}
+
+impl NoiseStage {
+    pub fn new(layers: Vec<(Vec2, f32)>) -> Self {
+        Self {
+            layers,
+            algorithm: NoiseAlgorithm::Perlin,
+            tiling: NoiseTiling::default(),
+            warp: None,
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: NoiseAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_tiling(mut self, tiling: NoiseTiling) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    pub fn with_warp(mut self, warp: Option<WarpSettings>) -> Self {
+        self.warp = warp;
+        self
+    }
+}
+
+impl TerrainStage for NoiseStage {
+    /// Only plain Perlin noise tiling across the map runs on the GPU.
+    fn gpu_stage(&self) -> Option<GpuStage> {
+        (self.algorithm == NoiseAlgorithm::Perlin
+            && self.tiling == NoiseTiling::default()
+            && self.warp.is_none())
+        .then(|| GpuStage::Noise(self.layers.clone()))
+    }
+
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, seed: u64) {
+        let mut noise = self.algorithm.layered(seed, &self.layers, self.tiling);
+        if let Some(warp) = &self.warp {
+            noise = warp.warp(noise, seed, self.tiling);
+        }
+
+        let (height, width) = height_map.dim();
+        par_map_indexed(height_map, |(yi, xi), _| {
+            let x = xi as f32 / width as f32;
+            let y = yi as f32 / height as f32;
+            noise.sample(vec2(x, y))
+        });
+    }
+}
@@ -0,0 +1,24 @@
+use ndarray::Array2;
+use ndarray_stats::QuantileExt;
+
+use crate::prelude::*;
+
+/// Linearly rescale the height map so it spans exactly [0, 1].
+pub struct NormaliseStage;
+
+impl TerrainStage for NormaliseStage {
+    fn gpu_stage(&self) -> Option<GpuStage> {
+        Some(GpuStage::Normalise)
+    }
+
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, _seed: u64) {
+        let min_value = *height_map.min().unwrap();
+        let max_value = *height_map.max().unwrap();
+        let range = max_value - min_value;
+        if range <= 0.0 {
+            height_map.fill(0.0);
+            return;
+        }
+        height_map.par_mapv_inplace(|x| (x - min_value) / range);
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+/// Shared between a running pipeline and whoever started it, to report progress and to ask it to stop early.
+#[derive(Default)]
+pub struct GenerationProgress {
+    completed_stages: AtomicUsize,
+    total_stages: AtomicUsize,
+    /// Fraction of the current stage finished, as the bits of an `f32`.
+    stage_fraction: AtomicU32,
+    cancelled: AtomicBool,
+}
+
+impl GenerationProgress {
+    /// Fraction of the stages finished so far, from 0 to 1, including any part of the current stage.
+    pub fn fraction(&self) -> f32 {
+        let total = self.total_stages.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        let stage_fraction = f32::from_bits(self.stage_fraction.load(Ordering::Relaxed));
+        (self.completed_stages.load(Ordering::Relaxed) as f32 + stage_fraction) / total as f32
+    }
+
+    /// Ask the pipeline to stop, at its next check between or within stages.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&self, total_stages: usize) {
+        self.total_stages.store(total_stages, Ordering::Relaxed);
+        self.completed_stages.store(0, Ordering::Relaxed);
+        self.stage_fraction.store(0, Ordering::Relaxed);
+    }
+
+    pub fn complete_stage(&self) {
+        self.stage_fraction.store(0, Ordering::Relaxed);
+        self.completed_stages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Report `done` of the current stage's `total` steps finished, returning `false` if the stage should stop early.
+    pub fn report(&self, done: usize, total: usize) -> bool {
+        let fraction = done as f32 / total.max(1) as f32;
+        self.stage_fraction
+            .store(fraction.min(1.0).to_bits(), Ordering::Relaxed);
+        !self.is_cancelled()
+    }
+}
@@ -0,0 +1,75 @@
+use ndarray::Array2;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+/// Trace rivers from high ground down to the sea and carve their beds into the height map.
+/// A cell is part of a river once enough of the land upstream of it drains through it.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RiverStage {
+    /// Number of upstream cells draining through a cell before it becomes a river.
+    pub threshold: f32,
+    /// Depth of a river bed where the river starts, growing with the amount of water carried.
+    pub carve_depth: f32,
+    /// Height of the sea, where rivers end.
+    /// Set from the terrain when generating, through `TerrainPipeline::with_sea_level`.
+    #[serde(skip)]
+    pub sea_level: f32,
+}
+
+impl Default for RiverStage {
+    fn default() -> Self {
+        Self {
+            threshold: 1000.0,
+            carve_depth: 0.005,
+            sea_level: SEA_LEVEL,
+        }
+    }
+}
+
+impl TerrainStage for RiverStage {
+    fn with_sea_level(&self, sea_level: f32) -> Option<Arc<dyn TerrainStage>> {
+        Some(Arc::new(Self {
+            sea_level,
+            ..self.clone()
+        }))
+    }
+
+    fn apply(&self, height_map: &mut Array2<f32>, layers: &mut TerrainLayers, seed: u64) {
+        self.apply_with_progress(height_map, layers, seed, &GenerationProgress::default());
+    }
+
+    /// Reports progress while finding how the map drains, which takes most of the time.
+    fn apply_with_progress(
+        &self,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        _seed: u64,
+        progress: &GenerationProgress,
+    ) {
+        let Some(drainage) = Drainage::with_progress(height_map, progress) else {
+            return;
+        };
+        let accumulation = drainage.flow_accumulation();
+
+        let mut rivers = Array2::zeros(height_map.dim());
+        for ((index, river), &flow) in rivers.indexed_iter_mut().zip(accumulation.iter()) {
+            let ground = height_map[index];
+            if flow < self.threshold || ground < self.sea_level {
+                continue;
+            }
+            *river = flow;
+
+            // Rivers flowing through filled depressions are lakes, which are left as they are
+            if drainage.filled[index] > ground {
+                continue;
+            }
+            let depth = self.carve_depth * (1.0 + portable::ln(flow / self.threshold));
+            height_map[index] = (ground - depth).max(self.sea_level);
+        }
+
+        layers.rivers = Some(rivers);
+    }
+}
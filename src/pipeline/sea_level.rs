@@ -0,0 +1,20 @@
+use ndarray::Array2;
+
+use crate::prelude::*;
+
+/// Flatten everything below the sea level to the sea level, giving a flat sea floor.
+pub struct SeaLevelClampStage {
+    pub sea_level: f32,
+}
+
+impl SeaLevelClampStage {
+    pub fn new(sea_level: f32) -> Self {
+        Self { sea_level }
+    }
+}
+
+impl TerrainStage for SeaLevelClampStage {
+    fn apply(&self, height_map: &mut Array2<f32>, _layers: &mut TerrainLayers, _seed: u64) {
+        height_map.par_mapv_inplace(|x| x.max(self.sea_level));
+    }
+}
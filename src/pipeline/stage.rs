@@ -0,0 +1,33 @@
+use ndarray::Array2;
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+/// A single step of terrain generation, operating in place on a height map.
+pub trait TerrainStage: Send + Sync {
+    /// Apply this stage to the height map, recording any additional output in the layers.
+    /// The seed is shared by all stages of a pipeline, so a generated map is fully determined by it.
+    fn apply(&self, height_map: &mut Array2<f32>, layers: &mut TerrainLayers, seed: u64);
+
+    /// As `apply`, reporting how far through the stage it is to `progress`, and stopping early if it is cancelled.
+    /// Stages which take a while override this, checking every so often; the rest run to the end.
+    fn apply_with_progress(
+        &self,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        seed: u64,
+        _progress: &GenerationProgress,
+    ) {
+        self.apply(height_map, layers, seed);
+    }
+
+    /// Copy of this stage working to a different sea level, if it depends on the sea level.
+    fn with_sea_level(&self, _sea_level: f32) -> Option<Arc<dyn TerrainStage>> {
+        None
+    }
+
+    /// Equivalent of this stage for `GpuTerrainGenerator`, if it has one.
+    fn gpu_stage(&self) -> Option<GpuStage> {
+        None
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use std::{ops::Range, sync::Arc};
+
+use crate::prelude::*;
+
+/// Ordered list of stages which together generate a height map.
+/// Stages are shared, so cloning the pipeline to run it on another thread is cheap.
+#[derive(Resource, Default, Clone)]
+pub struct TerrainPipeline {
+    pub stages: Vec<Arc<dyn TerrainStage>>,
+}
+
+impl TerrainPipeline {
+    pub fn new() -> Self {
+        Self { stages: Vec::new() }
+    }
+
+    /// Layered Perlin hills masked into a single circular island, then eroded, with rivers and lakes.
+    pub fn island() -> Self {
+        Self::new()
+            .with_stage(NoiseStage::new(vec![
+                (vec2(3.0, 3.0), 1.0),
+                (vec2(5.0, 5.0), 0.7),
+                (vec2(7.0, 7.0), 0.5),
+                (vec2(11.0, 11.0), 0.3),
+                (vec2(13.0, 13.0), 0.2),
+            ]))
+            .with_stage(NormaliseStage)
+            .with_stage(FalloffStage::new(vec2(0.5, 0.5), 0.25))
+            .with_stage(HydraulicErosionStage::default())
+            .with_stage(ThermalErosionStage::default())
+            .with_stage(RiverStage::default())
+            .with_stage(LakeStage::default())
+    }
+
+    /// Append a stage to the end of the pipeline.
+    pub fn with_stage(mut self, stage: impl TerrainStage + 'static) -> Self {
+        self.push(stage);
+        self
+    }
+
+    /// Append a stage to the end of the pipeline.
+    pub fn push(&mut self, stage: impl TerrainStage + 'static) {
+        self.stages.push(Arc::new(stage));
+    }
+
+    /// Insert a stage before the stage currently at `index`.
+    pub fn insert(&mut self, index: usize, stage: impl TerrainStage + 'static) {
+        self.stages.insert(index, Arc::new(stage));
+    }
+
+    /// Remove and return the stage at `index`.
+    pub fn remove(&mut self, index: usize) -> Arc<dyn TerrainStage> {
+        self.stages.remove(index)
+    }
+
+    /// Copy of this pipeline with every stage which depends on the sea level working to `sea_level`.
+    pub fn with_sea_level(&self, sea_level: f32) -> Self {
+        Self {
+            stages: self
+                .stages
+                .iter()
+                .map(|stage| stage.with_sea_level(sea_level).unwrap_or(stage.clone()))
+                .collect(),
+        }
+    }
+
+    /// Index of the first stage which depends on the sea level, or the number of stages if none does.
+    /// Only the stages from here on need to run again when the sea level changes.
+    pub fn sea_level_start(&self) -> usize {
+        self.stages
+            .iter()
+            .position(|stage| stage.with_sea_level(SEA_LEVEL).is_some())
+            .unwrap_or(self.stages.len())
+    }
+
+    /// Run every stage, in order, over an existing height map.
+    pub fn apply(&self, height_map: &mut Array2<f32>, layers: &mut TerrainLayers, seed: u64) {
+        for stage in &self.stages {
+            stage.apply(height_map, layers, seed);
+        }
+    }
+
+    /// Run every stage, in order, reporting progress through and between the stages to `progress`.
+    /// Stops early, returning `false`, if `progress` is cancelled.
+    pub fn apply_with_progress(
+        &self,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        seed: u64,
+        progress: &GenerationProgress,
+    ) -> bool {
+        progress.start(self.stages.len());
+        self.apply_stages_with_progress(0..self.stages.len(), height_map, layers, seed, progress)
+    }
+
+    /// Run the stages in a range, in order, as part of a run already started on `progress`.
+    /// Stops early, returning `false`, if `progress` is cancelled.
+    pub fn apply_stages_with_progress(
+        &self,
+        stages: Range<usize>,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        seed: u64,
+        progress: &GenerationProgress,
+    ) -> bool {
+        for stage in &self.stages[stages] {
+            if progress.is_cancelled() {
+                return false;
+            }
+            stage.apply_with_progress(height_map, layers, seed, progress);
+            // A cancelled stage may have stopped part way through
+            if progress.is_cancelled() {
+                return false;
+            }
+            progress.complete_stage();
+        }
+        true
+    }
+
+    /// Generate a new height map of the given size, along with any layers produced by the stages.
+    pub fn generate(&self, width: usize, height: usize, seed: u64) -> (Array2<f32>, TerrainLayers) {
+        let mut height_map = Array2::zeros((height, width));
+        let mut layers = TerrainLayers::default();
+        self.apply(&mut height_map, &mut layers, seed);
+        (height_map, layers)
+    }
+}
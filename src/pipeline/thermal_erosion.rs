@@ -0,0 +1,131 @@
+use ndarray::Array2;
+use serde::Deserialize;
+use std::f32::consts::SQRT_2;
+
+use crate::prelude::*;
+
+/// Thermal weathering.
+/// Material slumps from any slope steeper than the talus angle onto its lower neighbours,
+/// leaving scree slopes and softening sharp peaks.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ThermalErosionStage {
+    /// Number of passes over the whole map.
+    pub iterations: usize,
+    /// Steepest stable slope, in degrees.
+    pub talus_angle: f32,
+    /// Horizontal size of a cell, in the same units as the height map.
+    pub cell_size: f32,
+    /// Fraction of the excess material moved on each pass, from 0 to 1.
+    pub rate: f32,
+}
+
+impl Default for ThermalErosionStage {
+    fn default() -> Self {
+        Self {
+            iterations: 20,
+            talus_angle: 30.0,
+            cell_size: 0.001,
+            rate: 0.5,
+        }
+    }
+}
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+impl TerrainStage for ThermalErosionStage {
+    fn apply(&self, height_map: &mut Array2<f32>, layers: &mut TerrainLayers, seed: u64) {
+        self.apply_with_progress(height_map, layers, seed, &GenerationProgress::default());
+    }
+
+    /// Reports progress after every pass, each of which covers the whole map.
+    fn apply_with_progress(
+        &self,
+        height_map: &mut Array2<f32>,
+        layers: &mut TerrainLayers,
+        _seed: u64,
+        progress: &GenerationProgress,
+    ) {
+        let dim = height_map.dim();
+        let talus = portable::tan(self.talus_angle.to_radians()) * self.cell_size;
+        let erosion_map = layers.erosion_mut(dim);
+        // Material leaving each cell, and the total excess it is shared out by
+        let mut outflow = Array2::from_elem(dim, (0.0, 0.0));
+        let mut change = Array2::zeros(dim);
+
+        for iteration in 0..self.iterations {
+            if !progress.report(iteration, self.iterations) {
+                return;
+            }
+            // Every cell reads the heights from the start of the pass, so the result does not depend on visiting order.
+            // Cells gather what flows into them rather than scattering to their neighbours, so rows can run in parallel.
+            let heights = &*height_map;
+            par_map_indexed(&mut outflow, |index, _| {
+                let mut total_excess = 0.0;
+                let mut max_excess: f32 = 0.0;
+                for (neighbour, distance) in slope_neighbours(index, dim) {
+                    let excess = excess(heights, index, neighbour, distance, talus);
+                    total_excess += excess;
+                    max_excess = max_excess.max(excess);
+                }
+
+                // Moving half the largest excess levels that slope exactly, without overshooting
+                (self.rate * max_excess * 0.5, total_excess)
+            });
+            par_map_indexed(&mut change, |index, _| {
+                let mut change = -outflow[index].0;
+                for (neighbour, distance) in slope_neighbours(index, dim) {
+                    let excess = excess(heights, neighbour, index, distance, talus);
+                    if excess > 0.0 {
+                        let (moved, total_excess) = outflow[neighbour];
+                        change += moved * excess / total_excess;
+                    }
+                }
+                change
+            });
+            *height_map += &change;
+            *erosion_map += &change;
+        }
+    }
+}
+
+/// Neighbouring cells within the map, with their distances in cells.
+fn slope_neighbours(
+    (yi, xi): (usize, usize),
+    (height, width): (usize, usize),
+) -> impl Iterator<Item = ((usize, usize), f32)> {
+    NEIGHBOURS.iter().filter_map(move |(dy, dx)| {
+        let y = yi as isize + dy;
+        let x = xi as isize + dx;
+        if y < 0 || x < 0 || y >= height as isize || x >= width as isize {
+            return None;
+        }
+        let distance = if *dx != 0 && *dy != 0 { SQRT_2 } else { 1.0 };
+        Some(((y as usize, x as usize), distance))
+    })
+}
+
+/// Height by which the slope from one cell down to another exceeds the talus slope, or zero if it is stable.
+fn excess(
+    height_map: &Array2<f32>,
+    from: (usize, usize),
+    to: (usize, usize),
+    distance: f32,
+    talus: f32,
+) -> f32 {
+    let drop = height_map[from] - height_map[to];
+    if drop > talus * distance {
+        drop - talus * distance
+    } else {
+        0.0
+    }
+}
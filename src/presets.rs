@@ -0,0 +1,187 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    math::vec2,
+    prelude::*,
+    utils::BoxedFuture,
+};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::prelude::*;
+
+/// Terrain generation settings, loaded from a `.preset.ron` file in `assets/`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct TerrainPreset {
+    /// Generation stages, applied in order.
+    pub stages: Vec<StageDescriptor>,
+    /// Height below which cells are water.
+    #[serde(default = "default_sea_level")]
+    pub sea_level: f32,
+    /// Colour bands used to draw the land on the colour map.
+    pub colour_bands: Vec<ColourBand>,
+    /// Colour the open sea is drawn in.
+    #[serde(default = "TerrainPalette::default_ocean_colour")]
+    pub ocean_colour: [u8; 3],
+    /// Colour rivers are drawn in.
+    #[serde(default = "TerrainPalette::default_river_colour")]
+    pub river_colour: [u8; 3],
+    /// Colour lakes are drawn in.
+    #[serde(default = "TerrainPalette::default_lake_colour")]
+    pub lake_colour: [u8; 3],
+    /// Images used by mask stages, by path, loaded alongside the preset.
+    #[serde(skip)]
+    pub mask_images: Vec<(String, Handle<Image>)>,
+}
+
+/// Serialisable description of a single `TerrainStage`.
+#[derive(Deserialize, Debug, Clone)]
+pub enum StageDescriptor {
+    Noise {
+        layers: Vec<(Vec2, f32)>,
+        #[serde(default)]
+        algorithm: NoiseAlgorithm,
+        #[serde(default)]
+        tiling: NoiseTiling,
+        #[serde(default)]
+        warp: Option<WarpSettings>,
+    },
+    FractalNoise(FractalNoiseStage),
+    Normalise,
+    Falloff {
+        centre: [f32; 2],
+        radius: f32,
+        #[serde(default)]
+        shape: FalloffShape,
+    },
+    MaskImage {
+        path: String,
+    },
+    SeaLevelClamp {
+        sea_level: f32,
+    },
+    HydraulicErosion(HydraulicErosionStage),
+    ThermalErosion(ThermalErosionStage),
+    Rivers(RiverStage),
+    Lakes(LakeStage),
+    Archipelago(ArchipelagoStage),
+}
+
+impl TerrainPreset {
+    /// Parse a preset from the contents of a `.preset.ron` file, without loading its mask images.
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        // Allows `HydraulicErosion(iterations: 1000)` rather than `HydraulicErosion((iterations: 1000))`,
+        // and `warp: (strength: 0.1)` rather than `warp: Some((strength: 0.1))`
+        let options = ron::Options::default().with_default_extension(
+            Extensions::UNWRAP_VARIANT_NEWTYPES | Extensions::IMPLICIT_SOME,
+        );
+        options.from_bytes(bytes)
+    }
+
+    /// Build the generation pipeline described by this preset.
+    /// Mask images are looked up in `images`, and skipped if they have not loaded.
+    pub fn pipeline(&self, images: &Assets<Image>) -> TerrainPipeline {
+        let mut pipeline = TerrainPipeline::new();
+        for stage in &self.stages {
+            match stage {
+                StageDescriptor::Noise {
+                    layers,
+                    algorithm,
+                    tiling,
+                    warp,
+                } => pipeline.push(
+                    NoiseStage::new(layers.clone())
+                        .with_algorithm(*algorithm)
+                        .with_tiling(*tiling)
+                        .with_warp(*warp),
+                ),
+                StageDescriptor::FractalNoise(stage) => pipeline.push(stage.clone()),
+                StageDescriptor::Normalise => pipeline.push(NormaliseStage),
+                StageDescriptor::Falloff {
+                    centre,
+                    radius,
+                    shape,
+                } => pipeline.push(
+                    FalloffStage::new(vec2(centre[0], centre[1]), *radius).with_shape(*shape),
+                ),
+                StageDescriptor::MaskImage { path } => {
+                    let image = self
+                        .mask_images
+                        .iter()
+                        .find(|(mask_path, _)| mask_path == path)
+                        .and_then(|(_, handle)| images.get(handle));
+                    match image.and_then(MaskStage::from_image) {
+                        Some(stage) => pipeline.push(stage),
+                        None => warn!("Mask image {} is not available, skipping", path),
+                    }
+                }
+                StageDescriptor::SeaLevelClamp { sea_level } => {
+                    pipeline.push(SeaLevelClampStage::new(*sea_level))
+                }
+                StageDescriptor::HydraulicErosion(stage) => pipeline.push(stage.clone()),
+                StageDescriptor::ThermalErosion(stage) => pipeline.push(stage.clone()),
+                StageDescriptor::Rivers(stage) => pipeline.push(stage.clone()),
+                StageDescriptor::Lakes(stage) => pipeline.push(stage.clone()),
+                StageDescriptor::Archipelago(stage) => pipeline.push(stage.clone()),
+            }
+        }
+        pipeline
+    }
+
+    /// Build the colour palette described by this preset.
+    pub fn palette(&self) -> TerrainPalette {
+        TerrainPalette::new(
+            self.colour_bands.clone(),
+            self.ocean_colour,
+            self.river_colour,
+            self.lake_colour,
+        )
+    }
+}
+
+fn default_sea_level() -> f32 {
+    SEA_LEVEL
+}
+
+#[derive(Default)]
+pub struct TerrainPresetLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum TerrainPresetLoaderError {
+    #[error("Could not read preset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse preset: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for TerrainPresetLoader {
+    type Asset = TerrainPreset;
+    type Settings = ();
+    type Error = TerrainPresetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let mut preset = TerrainPreset::from_ron(&bytes)?;
+
+            for stage in &preset.stages {
+                if let StageDescriptor::MaskImage { path } = stage {
+                    let handle = load_context.load(path.clone());
+                    preset.mask_images.push((path.clone(), handle));
+                }
+            }
+            Ok(preset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["preset.ron"]
+    }
+}
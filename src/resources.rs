@@ -1,17 +1,360 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, tasks::Task};
 use ndarray::Array2;
+use serde::Deserialize;
+use std::sync::Arc;
 
 use crate::prelude::*;
 
+/// Terrain being generated in the background, which replaces `Terrain` once finished.
+#[derive(Resource, Default)]
+pub struct TerrainGeneration {
+    /// Running task, returning `None` if it was cancelled.
+    pub task: Option<Task<Option<Terrain>>>,
+    pub progress: Arc<GenerationProgress>,
+    pub seed: u64,
+    /// Whether the task only reruns the stages which depend on the sea level, over the current terrain.
+    pub water_only: bool,
+}
+
+impl TerrainGeneration {
+    /// Seed of the terrain being generated, or of the current terrain if nothing is.
+    pub fn latest_seed(&self, terrain: &Terrain) -> u64 {
+        if self.task.is_some() {
+            self.seed
+        } else {
+            terrain.seed
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct Terrain {
+    pub seed: u64,
     pub height_map: Array2<f32>,
+    pub layers: TerrainLayers,
+    /// Height below which cells are water.
+    pub sea_level: f32,
+    /// Cells below sea level which are connected to the open sea.
+    pub ocean_mask: Array2<bool>,
+    /// Cells at or above sea level.
+    pub land_mask: Array2<bool>,
+    /// Polylines, in cell coordinates, along which the height map crosses sea level.
+    pub coastlines: Vec<Vec<Vec2>>,
+    /// Identifier of the island each land cell belongs to, starting from 1, and zero in the water.
+    pub island_labels: Array2<u32>,
+    pub island_count: usize,
+    /// Fraction of the sky visible from each cell, for shading.
+    pub ambient_occlusion: Array2<f32>,
+    /// Height map and layers from just before the first stage which depends on the sea level,
+    /// for `regenerate_water` to rerun the remaining stages from when the sea level changes.
+    pub before_sea_level: Option<Arc<(Array2<f32>, TerrainLayers)>>,
+    /// Whether the heights were written straight into the height map texture by `GpuTerrainGenerator`,
+    /// and so need not be uploaded from here.
+    pub height_map_on_gpu: bool,
 }
 
 impl Terrain {
     pub fn new() -> Self {
+        let dim = (MAP_HEIGHT as usize, MAP_WIDTH as usize);
+        Self {
+            seed: 0,
+            height_map: Array2::zeros(dim),
+            layers: TerrainLayers::default(),
+            sea_level: SEA_LEVEL,
+            ocean_mask: Array2::from_elem(dim, false),
+            land_mask: Array2::from_elem(dim, false),
+            coastlines: Vec::new(),
+            island_labels: Array2::zeros(dim),
+            island_count: 0,
+            ambient_occlusion: Array2::ones(dim),
+            before_sea_level: None,
+            height_map_on_gpu: false,
+        }
+    }
+
+    /// Recompute the ambient occlusion from the ground and lake surfaces.
+    pub fn update_shading(&mut self) {
+        let surface =
+            Array2::from_shape_fn(self.height_map.dim(), |index| self.surface_height(index));
+        let (_, width) = self.height_map.dim();
+        self.ambient_occlusion = ambient_occlusion(
+            &surface,
+            AMBIENT_OCCLUSION_RADIUS,
+            TERRAIN_RELIEF * width as f32,
+        );
+    }
+
+    /// Recompute the ocean and land masks, coastlines and islands from the height map and sea level.
+    pub fn update_water(&mut self) {
+        self.ocean_mask = ocean_mask(&self.height_map, self.sea_level);
+        self.land_mask = self.height_map.mapv(|height| height >= self.sea_level);
+        self.coastlines = contour_lines(&self.height_map, self.sea_level);
+        (self.island_labels, self.island_count) = label_components(&self.land_mask);
+    }
+
+    /// Height of the ground, or of the flat surface of a lake.
+    pub fn surface_height(&self, index: (usize, usize)) -> f32 {
+        self.layers
+            .lake_surface(index)
+            .unwrap_or(self.height_map[index])
+    }
+
+    pub fn is_land(&self, index: (usize, usize)) -> bool {
+        self.land_mask[index]
+    }
+
+    pub fn is_ocean(&self, index: (usize, usize)) -> bool {
+        self.ocean_mask[index]
+    }
+
+    /// Water below sea level which is cut off from the open sea.
+    pub fn is_inland_water(&self, index: (usize, usize)) -> bool {
+        !self.land_mask[index] && !self.ocean_mask[index]
+    }
+
+    /// Coastlines as an SVG document, one path per polyline.
+    pub fn coastlines_svg(&self) -> String {
+        let (height, width) = self.height_map.dim();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\">\n",
+            width, height
+        );
+        for line in &self.coastlines {
+            let points: Vec<String> = line
+                .iter()
+                .map(|point| format!("{:.2},{:.2}", point.x, point.y))
+                .collect();
+            svg += &format!(
+                "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\" />\n",
+                points.join(" ")
+            );
+        }
+        svg += "</svg>\n";
+        svg
+    }
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Heights up to `max_height` are drawn in `colour`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct ColourBand {
+    pub max_height: f32,
+    pub colour: [u8; 3],
+}
+
+/// Colours used to draw the colour map.
+#[derive(Resource)]
+pub struct TerrainPalette {
+    /// Colour bands, in ascending order of height.
+    pub bands: Vec<ColourBand>,
+    pub ocean: [u8; 3],
+    pub river: [u8; 3],
+    pub lake: [u8; 3],
+}
+
+impl TerrainPalette {
+    const ABOVE_RANGE: [u8; 3] = [255, 0, 0];
+    const BELOW_RANGE: [u8; 3] = [255, 0, 255];
+
+    pub const COASTLINE: [u8; 3] = [40, 40, 40];
+
+    pub fn new(bands: Vec<ColourBand>, ocean: [u8; 3], river: [u8; 3], lake: [u8; 3]) -> Self {
+        Self {
+            bands,
+            ocean,
+            river,
+            lake,
+        }
+    }
+
+    pub fn default_ocean_colour() -> [u8; 3] {
+        [98, 165, 168]
+    }
+
+    pub fn default_river_colour() -> [u8; 3] {
+        [52, 110, 180]
+    }
+
+    pub fn default_lake_colour() -> [u8; 3] {
+        [70, 130, 170]
+    }
+
+    /// Colour of the first band containing the height.
+    pub fn colour(&self, height: f32) -> [u8; 3] {
+        if height.is_nan() || height < 0.0 {
+            return Self::BELOW_RANGE;
+        }
+        self.bands
+            .iter()
+            .find(|band| height <= band.max_height)
+            .map_or(Self::ABOVE_RANGE, |band| band.colour)
+    }
+}
+
+impl Default for TerrainPalette {
+    fn default() -> Self {
+        Self::new(
+            vec![
+                ColourBand {
+                    max_height: 0.2,
+                    colour: [98, 165, 168],
+                },
+                ColourBand {
+                    max_height: 0.4,
+                    colour: [213, 181, 157],
+                },
+                ColourBand {
+                    max_height: 0.6,
+                    colour: [152, 172, 92],
+                },
+                ColourBand {
+                    max_height: 0.8,
+                    colour: [101, 132, 66],
+                },
+                ColourBand {
+                    max_height: 1.0,
+                    colour: [110, 117, 136],
+                },
+            ],
+            Self::default_ocean_colour(),
+            Self::default_river_colour(),
+            Self::default_lake_colour(),
+        )
+    }
+}
+
+/// What the colour map shows.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerrainView {
+    /// Terrain coloured by height.
+    #[default]
+    Colour,
+    /// Material removed by erosion in red, and deposited in blue.
+    Erosion,
+}
+
+impl TerrainView {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Colour => Self::Erosion,
+            Self::Erosion => Self::Colour,
+        }
+    }
+}
+
+/// How the relief of the terrain shades it.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hillshade {
+    /// Flat colours, darkened only by cast shadows.
+    Off,
+    /// Lit by the sun, with cast shadows.
+    #[default]
+    Sun,
+    /// Lit from several directions at once, as in cartographic relief shading.
+    MultiDirectional,
+}
+
+impl Hillshade {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Sun,
+            Self::Sun => Self::MultiDirectional,
+            Self::MultiDirectional => Self::Off,
+        }
+    }
+}
+
+/// Extra information drawn over the colour map.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct TerrainOverlay {
+    pub coastlines: bool,
+}
+
+/// How the sun lights the terrain.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightModel {
+    /// Parallel rays from a sun infinitely far away, shining from its azimuth and elevation.
+    Directional,
+    /// Rays spreading out from a point above the map, at its position and height.
+    #[default]
+    Point,
+}
+
+impl LightModel {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Directional => Self::Point,
+            Self::Point => Self::Directional,
+        }
+    }
+}
+
+/// Light falling on the terrain.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Sun {
+    pub model: LightModel,
+    /// Direction of a directional sun, in degrees clockwise from the top of the map.
+    pub azimuth: f32,
+    /// Angle of a directional sun above the horizon, in degrees.
+    pub elevation: f32,
+    /// Position of a point light, as a fraction of the map size.
+    pub position: Vec2,
+    /// Height of a point light, in the same units as the height map.
+    pub height: f32,
+    /// Whether the sun follows the mouse, rather than only the keyboard.
+    pub follow_mouse: bool,
+}
+
+impl Default for Sun {
+    fn default() -> Self {
         Self {
-            height_map: Array2::zeros((MAP_HEIGHT as usize, MAP_WIDTH as usize)),
+            model: LightModel::Point,
+            azimuth: 315.0,
+            elevation: 45.0,
+            position: Vec2::new(0.5, 0.5),
+            height: SUN_HEIGHT,
+            follow_mouse: true,
+        }
+    }
+}
+
+impl Sun {
+    /// Lowest elevation of a directional sun following the mouse, at the edge of the map.
+    const MIN_FOLLOW_ELEVATION: f32 = 5.0;
+
+    /// Unit vector towards a directional sun, with x across the map, y down it and z up.
+    pub fn direction(&self) -> Vec3 {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            -elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+        )
+    }
+
+    /// Steer the sun towards a position on the map, as a fraction of the map size.
+    /// A point light moves there, while a directional sun shines from that side of the map,
+    /// overhead at the centre and lower towards the edges.
+    pub fn follow(&mut self, position: Vec2) {
+        match self.model {
+            LightModel::Point => self.position = position,
+            LightModel::Directional => {
+                let offset = position - Vec2::new(0.5, 0.5);
+                if offset != Vec2::ZERO {
+                    self.azimuth = offset.x.atan2(-offset.y).to_degrees().rem_euclid(360.0);
+                }
+                let distance = (offset.length() / 0.5).min(1.0);
+                self.elevation = 90.0 + (Self::MIN_FOLLOW_ELEVATION - 90.0) * distance;
+            }
         }
     }
 }
+
+/// Preset the terrain pipeline and palette are built from.
+#[derive(Resource)]
+pub struct TerrainPresetHandle(pub Handle<TerrainPreset>);
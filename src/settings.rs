@@ -1,5 +1,21 @@
 pub const MAP_WIDTH: u32 = 1024;
 pub const MAP_HEIGHT: u32 = 1024;
 
+pub const SEA_LEVEL: f32 = 0.2;
+pub const SEA_LEVEL_STEP: f32 = 0.01;
+
 pub const RENDER_WIDTH: f32 = 400.0;
 pub const RENDER_HEIGHT: f32 = RENDER_WIDTH;
+
+pub const SUN_HEIGHT: f32 = 1.5;
+pub const SUN_HEIGHT_STEP: f32 = 0.1;
+/// Degrees per second.
+pub const SUN_TURN_RATE: f32 = 90.0;
+/// Degrees per second.
+pub const SUN_RISE_RATE: f32 = 45.0;
+/// Height of the height map's top, as a fraction of the map width.
+pub const TERRAIN_RELIEF: f32 = 0.05;
+/// Distance in cells searched for the horizon.
+pub const AMBIENT_OCCLUSION_RADIUS: usize = 32;
+pub const SHADOW_MAP_SIZE: u32 = 512;
+pub const SHADOW_PENUMBRA: f32 = 0.05;
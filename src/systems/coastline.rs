@@ -0,0 +1,18 @@
+use bevy::prelude::*;
+
+use crate::prelude::*;
+
+/// Write the coastlines of the current terrain to an SVG file when X is pressed.
+pub fn export_coastlines(keyboard_input: Res<ButtonInput<KeyCode>>, terrain: Res<Terrain>) {
+    if keyboard_input.just_pressed(KeyCode::KeyX) {
+        let path = format!("coastlines_{}.svg", terrain.seed);
+        match std::fs::write(&path, terrain.coastlines_svg()) {
+            Ok(()) => info!(
+                "Exported {} coastlines to {}",
+                terrain.coastlines.len(),
+                path
+            ),
+            Err(error) => warn!("Unable to export coastlines to {}: {}", path, error),
+        }
+    }
+}
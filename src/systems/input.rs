@@ -18,36 +18,94 @@ pub fn get_cursor_coords(
     camera: &Camera,
     camera_transform: &GlobalTransform,
 ) -> Option<Vec2> {
-    if let Some(world_position) = window
+    window
         .cursor_position()
         .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
         .map(|ray| ray.origin.truncate())
-    {
-        let x = (world_position.x / RENDER_WIDTH) + 0.5;
-        let y = (world_position.y / RENDER_HEIGHT) + 0.5;
+        .map(|world_position| {
+            let x = (world_position.x / RENDER_WIDTH) + 0.5;
+            let y = (world_position.y / RENDER_HEIGHT) + 0.5;
 
-        return Some(Vec2::new(x, y));
-    }
-    return None;
+            Vec2::new(x, y)
+        })
 }
 
-pub fn update_sun_position(
+/// Steer the sun with the mouse, when it is following it.
+pub fn follow_mouse(
     window: Query<&Window, With<PrimaryWindow>>,
     camera: Query<(&Camera, &GlobalTransform)>,
-    query: Query<&Handle<CustomMaterial>>,
-    mut material_handle: ResMut<Assets<CustomMaterial>>,
+    mut sun: ResMut<Sun>,
 ) {
-    for material in query.iter() {
-        let material_id = material.id();
-        let material = material_handle.get_mut(material_id).unwrap();
+    if !sun.follow_mouse {
+        return;
+    }
+    let (camera, camera_transform) = camera.single();
+    if let Some(coords) = get_cursor_coords(window.single(), camera, camera_transform) {
+        let mut followed = *sun;
+        followed.follow(Vec2::new(coords.x, 1.0 - coords.y));
+        sun.set_if_neq(followed);
+    }
+}
+
+/// Switch the light model and mouse control, and turn, raise or lower the sun.
+pub fn sun_input_events(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut sun: ResMut<Sun>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        sun.model = sun.model.next();
+        info!("Light model: {:?}", sun.model);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        sun.follow_mouse = !sun.follow_mouse;
+    }
+
+    let seconds = time.delta_seconds();
+    let mut turn = 0.0;
+    if keyboard_input.pressed(KeyCode::ArrowLeft) {
+        turn -= SUN_TURN_RATE * seconds;
+    }
+    if keyboard_input.pressed(KeyCode::ArrowRight) {
+        turn += SUN_TURN_RATE * seconds;
+    }
+    if turn != 0.0 {
+        sun.azimuth = (sun.azimuth + turn).rem_euclid(360.0);
+    }
 
-        let sun_position = material.mouse_position.as_mut();
-        let (camera, camera_transform) = camera.single();
-        let coords = get_cursor_coords(window.single(), camera, camera_transform);
+    let mut rise = 0.0;
+    if keyboard_input.pressed(KeyCode::PageUp) {
+        rise += SUN_RISE_RATE * seconds;
+    }
+    if keyboard_input.pressed(KeyCode::PageDown) {
+        rise -= SUN_RISE_RATE * seconds;
+    }
+    if rise != 0.0 {
+        sun.elevation = (sun.elevation + rise).clamp(1.0, 90.0);
+    }
 
-        if let Some(coords) = coords {
-            sun_position[0] = coords.x;
-            sun_position[1] = 1.0 - coords.y;
+    if keyboard_input.just_pressed(KeyCode::Home) {
+        sun.height += SUN_HEIGHT_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::End) {
+        sun.height = (sun.height - SUN_HEIGHT_STEP).max(SUN_HEIGHT_STEP);
+    }
+}
+
+/// Pass the sun and hillshading on to the terrain shader.
+pub fn update_lighting_uniforms(
+    sun: Res<Sun>,
+    hillshade: Res<Hillshade>,
+    query: Query<&Handle<CustomMaterial>>,
+    mut materials: ResMut<Assets<CustomMaterial>>,
+) {
+    if !sun.is_changed() && !hillshade.is_changed() {
+        return;
+    }
+    for material in query.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.set_sun(&sun);
+            material.set_hillshade(*hillshade);
         }
     }
 }
@@ -1,5 +1,11 @@
+mod coastline;
 mod input;
+mod preset;
+mod seed;
 mod terrain;
 
+pub use coastline::*;
 pub use input::*;
+pub use preset::*;
+pub use seed::*;
 pub use terrain::*;
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+
+use crate::prelude::*;
+
+/// Rebuild the pipeline and palette whenever the preset, or any mask image it uses, is loaded or changed on disk,
+/// and regenerate the current island with them.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_terrain_preset(
+    mut preset_events: EventReader<AssetEvent<TerrainPreset>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut regenerate_terrain_events: EventWriter<RegenerateTerrain>,
+    mut pipeline: ResMut<TerrainPipeline>,
+    mut palette: ResMut<TerrainPalette>,
+    presets: Res<Assets<TerrainPreset>>,
+    images: Res<Assets<Image>>,
+    preset_handle: Res<TerrainPresetHandle>,
+    generation: Res<TerrainGeneration>,
+    mut terrain: ResMut<Terrain>,
+) {
+    let Some(preset) = presets.get(&preset_handle.0) else {
+        return;
+    };
+
+    // Sent on the first load and on every reload, once mask images have loaded too
+    let preset_changed = preset_events.read().any(|event| {
+        matches!(event, AssetEvent::LoadedWithDependencies { id } if *id == preset_handle.0.id())
+    });
+    let mask_changed = image_events.read().any(|event| match event {
+        AssetEvent::Modified { id } => preset
+            .mask_images
+            .iter()
+            .any(|(_, handle)| handle.id() == *id),
+        _ => false,
+    });
+    if !preset_changed && !mask_changed {
+        return;
+    }
+
+    info!("Applying terrain preset");
+    *pipeline = preset.pipeline(&images);
+    *palette = preset.palette();
+    terrain.sea_level = preset.sea_level;
+    regenerate_terrain_events.send(RegenerateTerrain::new(generation.latest_seed(&terrain)));
+}
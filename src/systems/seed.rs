@@ -0,0 +1,52 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::prelude::*;
+
+/// Show the seed and number of islands of the current terrain in the window title.
+pub fn display_seed(
+    mut events: EventReader<RedrawTerrain>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+    terrain: Res<Terrain>,
+) {
+    for _ in events.read() {
+        info!(
+            "Terrain seed: {}, islands: {}",
+            terrain.seed, terrain.island_count
+        );
+        for mut window in window.iter_mut() {
+            window.title = format!(
+                "Islands - seed {} - {} islands",
+                terrain.seed, terrain.island_count
+            );
+        }
+    }
+}
+
+/// Show the progress of any terrain generation running in the background in the window title.
+pub fn display_progress(
+    generation: Res<TerrainGeneration>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if generation.task.is_none() {
+        return;
+    }
+    for mut window in window.iter_mut() {
+        window.title = format!(
+            "Islands - generating seed {} - {:.0}%",
+            generation.seed,
+            generation.progress.fraction() * 100.0
+        );
+    }
+}
+
+/// Copy the seed of the current terrain to the clipboard when C is pressed.
+pub fn copy_seed(keyboard_input: Res<ButtonInput<KeyCode>>, terrain: Res<Terrain>) {
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        let result = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(terrain.seed.to_string()));
+        match result {
+            Ok(()) => info!("Copied seed {} to clipboard", terrain.seed),
+            Err(error) => warn!("Unable to copy seed to clipboard: {}", error),
+        }
+    }
+}
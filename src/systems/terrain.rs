@@ -1,63 +1,234 @@
-use bevy::{math::vec2, prelude::*};
+use bevy::{
+    prelude::*,
+    tasks::{block_on, poll_once, AsyncComputeTaskPool},
+};
 use ndarray::Array2;
-use ndarray_stats::QuantileExt;
+use std::sync::Arc;
 
 use crate::prelude::*;
 
+#[allow(clippy::too_many_arguments)]
 pub fn input_events(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut events: EventWriter<RegenerateTerrain>,
+    mut water_events: EventWriter<RegenerateWater>,
+    mut redraw_terrain_events: EventWriter<RedrawTerrain>,
+    mut view: ResMut<TerrainView>,
+    mut overlay: ResMut<TerrainOverlay>,
+    mut hillshade: ResMut<Hillshade>,
+    mut terrain: ResMut<Terrain>,
 ) {
     if keyboard_input.just_pressed(KeyCode::Space) {
-        events.send(RegenerateTerrain);
+        events.send(RegenerateTerrain::random());
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        *view = view.next();
+        redraw_terrain_events.send(RedrawTerrain);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        *hillshade = hillshade.next();
+        info!("Hillshade: {:?}", *hillshade);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        overlay.coastlines = !overlay.coastlines;
+        redraw_terrain_events.send(RedrawTerrain);
+    }
+
+    // Raise or lower the sea
+    let mut sea_level_change = 0.0;
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        sea_level_change += SEA_LEVEL_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        sea_level_change -= SEA_LEVEL_STEP;
+    }
+    if sea_level_change != 0.0 {
+        terrain.sea_level = (terrain.sea_level + sea_level_change).clamp(0.0, 1.0);
+        terrain.update_water();
+        info!("Sea level: {:.2}", terrain.sea_level);
+        redraw_terrain_events.send(RedrawTerrain);
+
+        // Rivers and lakes depend on the sea level, so regenerate them in the background
+        water_events.send(RegenerateWater);
     }
 }
 
+/// Start generating new terrain in the background, cancelling any generation still running.
+/// The current terrain stays on screen until `receive_terrain` swaps the new one in.
 pub fn regenerate_terrain(
     mut regenerate_terrain_events: EventReader<RegenerateTerrain>,
-    mut redraw_terrain_events: EventWriter<RedrawTerrain>,
-    mut terrain: ResMut<Terrain>,
+    mut generation: ResMut<TerrainGeneration>,
+    terrain: Res<Terrain>,
+    pipeline: Res<TerrainPipeline>,
+    gpu: Option<Res<GpuTerrainGenerator>>,
+    height_map_texture: Option<Res<HeightMapTexture>>,
 ) {
-    for _ in regenerate_terrain_events.read() {
-        // Generate hills
-        let perlin_noise_generator = PerlinNoise::new(vec![
-            ((3, 3), 1.0),
-            ((5, 5), 0.7),
-            ((7, 7), 0.5),
-            ((11, 11), 0.3),
-            ((13, 13), 0.2),
-        ]);
-        for yi in 0..MAP_HEIGHT {
-            let y = yi as f32 / MAP_HEIGHT as f32;
-            for xi in 0..MAP_WIDTH {
-                let x = xi as f32 / MAP_WIDTH as f32;
-                let height = perlin_noise_generator.sample(vec2(x as f32, y as f32));
-                terrain.height_map[(yi as usize, xi as usize)] = height;
-            }
+    // Only the latest request matters
+    let Some(event) = regenerate_terrain_events.read().last() else {
+        return;
+    };
+    if generation.task.is_some() {
+        info!("Cancelling terrain generation for seed {}", generation.seed);
+        generation.progress.cancel();
+    }
+
+    let progress = Arc::new(GenerationProgress::default());
+    let pipeline = pipeline.with_sea_level(terrain.sea_level);
+    let gpu = gpu.map(|gpu| gpu.clone());
+    // Heights only go straight to the screen when no CPU stage changes them afterwards
+    let target = height_map_texture
+        .filter(|_| {
+            pipeline
+                .stages
+                .iter()
+                .all(|stage| stage.gpu_stage().is_some())
+        })
+        .and_then(|texture| texture.texture());
+    let (seed, sea_level) = (event.seed, terrain.sea_level);
+    let task_progress = progress.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let mut terrain = Terrain::new();
+        terrain.seed = seed;
+        terrain.sea_level = sea_level;
+
+        // Leading stages with GPU equivalents run there, when enabled
+        let gpu_stages = gpu.map_or(0, |gpu| {
+            gpu.apply(&pipeline, &mut terrain.height_map, seed, target.as_ref())
+                .unwrap_or_else(|error| {
+                    warn!("{}, generating on the CPU instead", error);
+                    0
+                })
+        });
+        terrain.height_map_on_gpu = target.is_some() && gpu_stages > 0;
+        let sea_level_start = pipeline.sea_level_start().max(gpu_stages) - gpu_stages;
+        let pipeline = TerrainPipeline {
+            stages: pipeline.stages[gpu_stages..].to_vec(),
+        };
+
+        // Keep the terrain from before the sea level matters, to rerun only the rest when it changes
+        task_progress.start(pipeline.stages.len());
+        if !pipeline.apply_stages_with_progress(
+            0..sea_level_start,
+            &mut terrain.height_map,
+            &mut terrain.layers,
+            seed,
+            &task_progress,
+        ) {
+            return None;
+        }
+        terrain.before_sea_level = Some(Arc::new((
+            terrain.height_map.clone(),
+            terrain.layers.clone(),
+        )));
+        if !pipeline.apply_stages_with_progress(
+            sea_level_start..pipeline.stages.len(),
+            &mut terrain.height_map,
+            &mut terrain.layers,
+            seed,
+            &task_progress,
+        ) {
+            return None;
         }
+        terrain.update_shading();
+        terrain.update_water();
+        Some(terrain)
+    });
 
-        // Normalize the height map
-        let min_value = *terrain.height_map.min().unwrap();
-        let max_value = *terrain.height_map.max().unwrap();
-        terrain
-            .height_map
-            .mapv_inplace(|x| (x - min_value) / (max_value - min_value));
-
-        // Circular island
-        let center = vec2(MAP_WIDTH as f32 * 0.5, MAP_HEIGHT as f32 * 0.5);
-        let radius = MAP_WIDTH as f32 * 0.25;
-        for yi in 0..MAP_HEIGHT {
-            for xi in 0..MAP_WIDTH {
-                let position = vec2(xi as f32, yi as f32);
-                let distance = (position - center).length();
-                let scale = (-0.5 * (distance / radius).powi(2)).exp();
-                terrain.height_map[(yi as usize, xi as usize)] *= scale;
-            }
+    *generation = TerrainGeneration {
+        task: Some(task),
+        progress,
+        seed,
+        water_only: false,
+    };
+}
+
+/// Rerun the stages which depend on the sea level in the background, from the current terrain's cached height map.
+/// Terrain still being generated is left to finish, and has its water regenerated once it arrives.
+pub fn regenerate_water(
+    mut regenerate_water_events: EventReader<RegenerateWater>,
+    mut generation: ResMut<TerrainGeneration>,
+    terrain: Res<Terrain>,
+    pipeline: Res<TerrainPipeline>,
+) {
+    if regenerate_water_events.read().count() == 0 {
+        return;
+    }
+    if generation.task.is_some() {
+        if !generation.water_only {
+            return;
+        }
+        generation.progress.cancel();
+    }
+    let Some(before_sea_level) = terrain.before_sea_level.clone() else {
+        return;
+    };
+    let pipeline = pipeline.with_sea_level(terrain.sea_level);
+    let stages = pipeline.sea_level_start()..pipeline.stages.len();
+    if stages.is_empty() {
+        return;
+    }
+
+    let progress = Arc::new(GenerationProgress::default());
+    let (seed, sea_level) = (terrain.seed, terrain.sea_level);
+    let task_progress = progress.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let mut terrain = Terrain::new();
+        terrain.seed = seed;
+        terrain.sea_level = sea_level;
+        (terrain.height_map, terrain.layers) = (*before_sea_level).clone();
+        terrain.before_sea_level = Some(before_sea_level);
+
+        task_progress.start(stages.len());
+        if !pipeline.apply_stages_with_progress(
+            stages,
+            &mut terrain.height_map,
+            &mut terrain.layers,
+            seed,
+            &task_progress,
+        ) {
+            return None;
         }
+        terrain.update_shading();
+        terrain.update_water();
+        Some(terrain)
+    });
 
-        // Trigger terrain redraw
-        redraw_terrain_events.send(RedrawTerrain);
+    *generation = TerrainGeneration {
+        task: Some(task),
+        progress,
+        seed,
+        water_only: true,
+    };
+}
+
+/// Swap in newly generated terrain once its task has finished, and redraw it.
+pub fn receive_terrain(
+    mut generation: ResMut<TerrainGeneration>,
+    mut redraw_terrain_events: EventWriter<RedrawTerrain>,
+    mut water_events: EventWriter<RegenerateWater>,
+    mut terrain: ResMut<Terrain>,
+) {
+    let Some(task) = generation.task.as_mut() else {
+        return;
+    };
+    let Some(result) = block_on(poll_once(task)) else {
+        return;
+    };
+    generation.task = None;
+    let Some(mut generated) = result else {
+        return;
+    };
+
+    // Keep any change to the sea level made while generating, then catch the rivers and lakes up with it
+    if generated.sea_level != terrain.sea_level {
+        generated.sea_level = terrain.sea_level;
+        generated.update_water();
+        water_events.send(RegenerateWater);
     }
+    *terrain = generated;
+
+    // Trigger terrain redraw
+    redraw_terrain_events.send(RedrawTerrain);
 }
 
 pub fn redraw_height_map(
@@ -71,34 +242,105 @@ pub fn redraw_height_map(
         for material in query.iter() {
             let material_id = material.id();
             let material = material_handle.get_mut(material_id).unwrap();
-            if let Some(height_map_handle) = material.height_map.as_ref() {
+            material.sea_level = terrain.sea_level;
+            if terrain.height_map_on_gpu {
+                // Already in the texture, so uploading it again would only replace it
+            } else if let Some(height_map_handle) = material.height_map.as_ref() {
                 let height_map = texture_handle.get_mut(height_map_handle).unwrap();
-                render_height_map(&terrain.height_map, &mut height_map.data);
+                render_height_map(&terrain, &mut height_map.data);
+            }
+            if let Some(occlusion_handle) = material.ambient_occlusion.as_ref() {
+                let occlusion = texture_handle.get_mut(occlusion_handle).unwrap();
+                for (texel, visible) in occlusion
+                    .data
+                    .iter_mut()
+                    .zip(terrain.ambient_occlusion.iter())
+                {
+                    *texel = (visible * 255.0) as u8;
+                }
             }
         }
     }
 }
 
-fn render_height_map(height_map: &Array2<f32>, data: &mut [u8]) {
+fn render_height_map(terrain: &Terrain, data: &mut [u8]) {
     for y in 0..MAP_HEIGHT {
         for x in 0..MAP_WIDTH {
-            let height = height_map[(y as usize, x as usize)];
+            // Lakes are shaded as their flat water surface rather than the lake bed
+            let height = terrain.surface_height((y as usize, x as usize));
 
             let index = (y * MAP_WIDTH + x) as usize * 4;
-            data[index] = (height * 255.0) as u8;
-            data[index + 1] = (height * 255.0) as u8;
-            data[index + 2] = (height * 255.0) as u8;
-            data[index + 3] = 255;
+            data[index..index + 4].copy_from_slice(&height.to_le_bytes());
+        }
+    }
+}
+
+/// Recompute which parts of the terrain are in shadow, when the terrain, sea level or sun changes.
+/// The sea is flattened to its surface, as in the shader, so the sea bed neither casts nor catches shadows.
+pub fn redraw_shadow_map(
+    mut events: EventReader<RedrawTerrain>,
+    query: Query<&Handle<CustomMaterial>>,
+    materials: Res<Assets<CustomMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    terrain: Res<Terrain>,
+    sun: Res<Sun>,
+) {
+    let redraw = events.read().count() > 0;
+    if !redraw && !sun.is_changed() {
+        return;
+    }
+    for material in query.iter() {
+        let Some(material) = materials.get(material) else {
+            continue;
+        };
+        let Some(shadow_map) = material
+            .shadow_map
+            .as_ref()
+            .and_then(|handle| images.get_mut(handle))
+        else {
+            continue;
+        };
+
+        // Shadows are softened anyway, so a coarser grid than the height map will do.
+        // Heights are measured in cells of the shadow map, with the same relief as the shader lights.
+        let step = (MAP_WIDTH / SHADOW_MAP_SIZE) as usize;
+        let scale = TERRAIN_RELIEF * SHADOW_MAP_SIZE as f32;
+        let heights = Array2::from_shape_fn(
+            (SHADOW_MAP_SIZE as usize, SHADOW_MAP_SIZE as usize),
+            |(y, x)| {
+                terrain
+                    .surface_height((y * step, x * step))
+                    .max(terrain.sea_level)
+                    * scale
+            },
+        );
+        let penumbra = SHADOW_PENUMBRA * scale;
+        let visibility = match sun.model {
+            LightModel::Directional => {
+                directional_light_visibility(&heights, sun.direction(), penumbra)
+            }
+            LightModel::Point => point_light_visibility(
+                &heights,
+                (sun.position * SHADOW_MAP_SIZE as f32).extend(sun.height * scale),
+                penumbra,
+            ),
+        };
+        for (texel, visibility) in shadow_map.data.iter_mut().zip(visibility.iter()) {
+            *texel = (visibility * 255.0) as u8;
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn redraw_colour_map(
     mut events: EventReader<RedrawTerrain>,
     query: Query<&Handle<CustomMaterial>>,
     mut material_handle: ResMut<Assets<CustomMaterial>>,
     mut texture_handle: ResMut<Assets<Image>>,
     terrain: Res<Terrain>,
+    palette: Res<TerrainPalette>,
+    view: Res<TerrainView>,
+    overlay: Res<TerrainOverlay>,
 ) {
     for _ in events.read() {
         for material in query.iter() {
@@ -106,16 +348,65 @@ pub fn redraw_colour_map(
             let material = material_handle.get_mut(material_id).unwrap();
             if let Some(colour_map_handle) = material.colour_map.as_ref() {
                 let colour_map = texture_handle.get_mut(colour_map_handle).unwrap();
-                render_colour_map(&terrain.height_map, &mut colour_map.data);
+                match (*view, terrain.layers.erosion.as_ref()) {
+                    (TerrainView::Erosion, Some(erosion_map)) => {
+                        render_erosion_map(erosion_map, &mut colour_map.data)
+                    }
+                    _ => render_colour_map(&terrain, &palette, &mut colour_map.data),
+                }
+                if overlay.coastlines {
+                    render_coastlines(&terrain.coastlines, &mut colour_map.data);
+                }
             }
         }
     }
 }
 
-fn render_colour_map(height_map: &Array2<f32>, data: &mut [u8]) {
+fn render_colour_map(terrain: &Terrain, palette: &TerrainPalette, data: &mut [u8]) {
     for y in 0..MAP_HEIGHT {
         for x in 0..MAP_WIDTH {
-            let colour = get_colour(height_map[(y as usize, x as usize)]);
+            let index = (y as usize, x as usize);
+            let is_river = terrain
+                .layers
+                .rivers
+                .as_ref()
+                .is_some_and(|rivers| rivers[index] > 0.0);
+            let is_lake = terrain.layers.lake_surface(index).is_some();
+            let colour = if terrain.is_ocean(index) {
+                palette.ocean
+            } else if is_lake || terrain.is_inland_water(index) {
+                palette.lake
+            } else if is_river {
+                palette.river
+            } else {
+                palette.colour(terrain.height_map[index])
+            };
+            // Transparent where the shader draws water below sea level, over its shallow colour
+            let alpha = if terrain.is_land(index) { 255 } else { 0 };
+
+            let index = (y * MAP_WIDTH + x) as usize * 4;
+            data[index] = colour[0];
+            data[index + 1] = colour[1];
+            data[index + 2] = colour[2];
+            data[index + 3] = alpha;
+        }
+    }
+}
+
+fn render_erosion_map(erosion_map: &Array2<f32>, data: &mut [u8]) {
+    // Scale so the largest change is fully saturated
+    let max_change = erosion_map
+        .iter()
+        .fold(f32::EPSILON, |max, change| max.max(change.abs()));
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let change = erosion_map[(y as usize, x as usize)] / max_change;
+            let fade = (255.0 * (1.0 - change.abs().sqrt())) as u8;
+            let colour = if change < 0.0 {
+                [255, fade, fade]
+            } else {
+                [fade, fade, 255]
+            };
 
             let index = (y * MAP_WIDTH + x) as usize * 4;
             data[index] = colour[0];
@@ -126,14 +417,33 @@ fn render_colour_map(height_map: &Array2<f32>, data: &mut [u8]) {
     }
 }
 
-fn get_colour(height: f32) -> [u8; 3] {
-    match height {
-        0.0..=0.2 => [98, 165, 168],
-        0.2..=0.4 => [213, 181, 157],
-        0.4..=0.6 => [152, 172, 92],
-        0.6..=0.8 => [101, 132, 66],
-        0.8..=1.0 => [110, 117, 136],
-        1.0..=2.0 => [255, 0, 0],
-        _ => [255, 0, 255],
+fn render_coastlines(coastlines: &[Vec<Vec2>], data: &mut [u8]) {
+    for line in coastlines {
+        for segment in line.windows(2) {
+            // Step along the segment at most one pixel at a time
+            let steps = (segment[1] - segment[0])
+                .abs()
+                .max_element()
+                .ceil()
+                .max(1.0) as usize;
+            for step in 0..=steps {
+                let point = segment[0]
+                    .lerp(segment[1], step as f32 / steps as f32)
+                    .round();
+                if point.x < 0.0
+                    || point.y < 0.0
+                    || point.x >= MAP_WIDTH as f32
+                    || point.y >= MAP_HEIGHT as f32
+                {
+                    continue;
+                }
+
+                let index = (point.y as u32 * MAP_WIDTH + point.x as u32) as usize * 4;
+                data[index] = TerrainPalette::COASTLINE[0];
+                data[index + 1] = TerrainPalette::COASTLINE[1];
+                data[index + 2] = TerrainPalette::COASTLINE[2];
+                data[index + 3] = 255;
+            }
+        }
     }
 }
@@ -0,0 +1,29 @@
+use ndarray::Array2;
+
+use crate::prelude::*;
+
+/// Label each connected region of `true` cells, touching sides or corners, with its own identifier starting from 1.
+/// Cells outside every region are labelled 0. Also returns the number of regions.
+pub fn label_components(mask: &Array2<bool>) -> (Array2<u32>, usize) {
+    let dim = mask.dim();
+    let mut labels = Array2::zeros(dim);
+    let mut count = 0;
+    let mut stack = Vec::new();
+    for (start, &is_set) in mask.indexed_iter() {
+        if !is_set || labels[start] != 0 {
+            continue;
+        }
+        count += 1;
+        labels[start] = count as u32;
+        stack.push(start);
+        while let Some(index) = stack.pop() {
+            for neighbour in neighbours(index, dim) {
+                if mask[neighbour] && labels[neighbour] == 0 {
+                    labels[neighbour] = count as u32;
+                    stack.push(neighbour);
+                }
+            }
+        }
+    }
+    (labels, count)
+}
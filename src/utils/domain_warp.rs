@@ -0,0 +1,96 @@
+use bevy::{math::vec2, prelude::*};
+use std::ops::RangeInclusive;
+
+use crate::prelude::*;
+
+/// Samples a noise source at positions pushed around by a second noise field, bending its features into
+/// swirls and folds.
+/// With more than one iteration the warp field warps itself first, `f(p + s w(p + s w(p)))`, as described by
+/// Inigo Quilez.
+pub struct DomainWarp {
+    pub source: Box<dyn NoiseSource>,
+    /// Noise field giving the offset, sampled twice per iteration for the `x` and `y` components.
+    pub warp: Box<dyn NoiseSource>,
+    /// Largest offset, in the source's units, reached when the warp field is at the end of its range.
+    pub strength: f32,
+    pub iterations: u32,
+}
+
+impl DomainWarp {
+    /// Shift between the samples giving the `x` and `y` offsets, so the two components are unrelated.
+    const COMPONENT_SHIFT: Vec2 = vec2(5.2, 1.3);
+
+    pub fn new(source: Box<dyn NoiseSource>, warp: Box<dyn NoiseSource>, strength: f32) -> Self {
+        Self {
+            source,
+            warp,
+            strength,
+            iterations: 1,
+        }
+    }
+
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Position the source is sampled at in place of `position`.
+    pub fn warped(&self, position: Vec2) -> Vec2 {
+        let scale = self.scale();
+        let mut offset = Vec2::ZERO;
+        for _ in 0..self.iterations {
+            let warped = position + offset;
+            offset = vec2(
+                self.warp.sample(warped),
+                self.warp.sample(warped + Self::COMPONENT_SHIFT),
+            ) * scale;
+        }
+        position + offset
+    }
+
+    /// Position the source is sampled at in place of `position`, and its Jacobian with respect to `position`.
+    pub fn warped_with_jacobian(&self, position: Vec2) -> (Vec2, Mat2) {
+        let scale = self.scale();
+        let mut offset = Vec2::ZERO;
+        let mut jacobian = Mat2::IDENTITY;
+        for _ in 0..self.iterations {
+            let warped = position + offset;
+            let (x, x_gradient) = self.warp.sample_with_gradient(warped);
+            let (y, y_gradient) = self
+                .warp
+                .sample_with_gradient(warped + Self::COMPONENT_SHIFT);
+            offset = vec2(x, y) * scale;
+
+            // Rows of the warp field's Jacobian are the gradients of its components
+            let field = Mat2::from_cols(
+                vec2(x_gradient.x, y_gradient.x),
+                vec2(x_gradient.y, y_gradient.y),
+            );
+            jacobian = Mat2::IDENTITY + field * jacobian * scale;
+        }
+        (position + offset, jacobian)
+    }
+
+    /// Scale bringing the largest value of the warp field to `strength`.
+    fn scale(&self) -> f32 {
+        let range = self.warp.range();
+        let extent = range.start().abs().max(range.end().abs()).max(f32::EPSILON);
+        self.strength / extent
+    }
+}
+
+impl NoiseSource for DomainWarp {
+    fn sample(&self, position: Vec2) -> f32 {
+        self.source.sample(self.warped(position))
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let (warped, jacobian) = self.warped_with_jacobian(position);
+        let (value, gradient) = self.source.sample_with_gradient(warped);
+        (value, jacobian.transpose() * gradient)
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        self.source.range()
+    }
+}
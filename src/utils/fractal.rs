@@ -0,0 +1,183 @@
+use bevy::{math::vec2, prelude::*};
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+
+use crate::prelude::*;
+
+/// How the octaves of a `Fractal` are combined.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FractalKind {
+    /// Fractional Brownian motion, a plain weighted sum of octaves, for rolling hills.
+    #[default]
+    Fbm,
+    /// Ridged multifractal, sharp crests where the noise crosses zero, for mountain ranges.
+    /// Each octave is weighted by the one before, so detail gathers on the ridges.
+    Ridged,
+    /// Absolute value of each octave rescaled to -1 to 1, for rounded, puffy hills.
+    Billow,
+    /// Absolute value of each octave, creased in the valleys.
+    Turbulence,
+    /// fBm with each octave damped by the slope of the octaves before it, `1 / (1 + gain |d|^2)`,
+    /// so detail fades on steep slopes and collects in valleys and on plateaus, as if eroded.
+    /// `d` is the sum of the octave gradients in the source's own coordinates, as in Inigo Quilez's version.
+    Eroded,
+}
+
+/// Octave parameters of a `Fractal`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct FractalSettings {
+    pub kind: FractalKind,
+    /// Number of octaves summed.
+    pub octaves: u32,
+    /// Frequency multiplier from one octave to the next.
+    pub lacunarity: f32,
+    /// Amplitude multiplier from one octave to the next.
+    pub persistence: f32,
+    /// How strongly each ridge sharpens the next octave for `Ridged`,
+    /// or how strongly slopes damp detail for `Eroded`.
+    pub gain: f32,
+    /// Height of the ridge crests before squaring, for `Ridged` only.
+    pub offset: f32,
+}
+
+impl Default for FractalSettings {
+    fn default() -> Self {
+        Self {
+            kind: FractalKind::Fbm,
+            octaves: 6,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            gain: 2.0,
+            offset: 1.0,
+        }
+    }
+}
+
+/// Octaves of a noise source at rising frequencies and falling amplitudes.
+pub struct Fractal {
+    pub source: Box<dyn NoiseSource>,
+    pub settings: FractalSettings,
+}
+
+impl Fractal {
+    /// Shift between octaves, so the lattices of successive octaves do not line up at the origin.
+    const OCTAVE_SHIFT: Vec2 = vec2(17.31, 9.77);
+
+    pub fn new(source: Box<dyn NoiseSource>, settings: FractalSettings) -> Self {
+        Self { source, settings }
+    }
+
+    /// Sum of the octave amplitudes.
+    fn total_amplitude(&self) -> f32 {
+        (0..self.settings.octaves)
+            .map(|octave| self.settings.persistence.powi(octave as i32))
+            .sum()
+    }
+
+    /// Largest absolute value the source returns.
+    fn source_extent(&self) -> f32 {
+        let range = self.source.range();
+        range.start().abs().max(range.end().abs())
+    }
+}
+
+impl NoiseSource for Fractal {
+    fn sample(&self, position: Vec2) -> f32 {
+        let settings = &self.settings;
+        let mut value = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut weight = 1.0;
+        let mut slope = Vec2::ZERO;
+        for octave in 0..settings.octaves {
+            let octave_position = position * frequency + Self::OCTAVE_SHIFT * octave as f32;
+            let noise = if settings.kind == FractalKind::Eroded {
+                let (noise, gradient) = self.source.sample_with_gradient(octave_position);
+                slope += gradient;
+                noise / (1.0 + settings.gain * slope.length_squared())
+            } else {
+                self.source.sample(octave_position)
+            };
+            value += amplitude
+                * match settings.kind {
+                    FractalKind::Fbm | FractalKind::Eroded => noise,
+                    FractalKind::Billow => 2.0 * noise.abs() - 1.0,
+                    FractalKind::Turbulence => noise.abs(),
+                    FractalKind::Ridged => {
+                        let signal = (settings.offset - noise.abs()).powi(2) * weight;
+                        weight = (signal * settings.gain).clamp(0.0, 1.0);
+                        signal
+                    }
+                };
+            frequency *= settings.lacunarity;
+            amplitude *= settings.persistence;
+        }
+        value
+    }
+
+    /// Exact for every kind except `Eroded`, which needs second derivatives and is estimated instead.
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let settings = &self.settings;
+        if settings.kind == FractalKind::Eroded {
+            return central_differences(|position| self.sample(position), position);
+        }
+
+        let mut value = 0.0;
+        let mut gradient = Vec2::ZERO;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut weight = 1.0;
+        let mut weight_gradient = Vec2::ZERO;
+        for octave in 0..settings.octaves {
+            let (noise, noise_gradient) = self
+                .source
+                .sample_with_gradient(position * frequency + Self::OCTAVE_SHIFT * octave as f32);
+            let noise_gradient = noise_gradient * frequency;
+            let (octave_value, octave_gradient) = match settings.kind {
+                FractalKind::Fbm | FractalKind::Eroded => (noise, noise_gradient),
+                FractalKind::Billow => (
+                    2.0 * noise.abs() - 1.0,
+                    2.0 * noise.signum() * noise_gradient,
+                ),
+                FractalKind::Turbulence => (noise.abs(), noise.signum() * noise_gradient),
+                FractalKind::Ridged => {
+                    let crest = settings.offset - noise.abs();
+                    let signal = crest.powi(2) * weight;
+                    let signal_gradient = -2.0 * crest * noise.signum() * noise_gradient * weight
+                        + crest.powi(2) * weight_gradient;
+                    let unclamped = signal * settings.gain;
+                    weight = unclamped.clamp(0.0, 1.0);
+                    weight_gradient = if unclamped > 0.0 && unclamped < 1.0 {
+                        signal_gradient * settings.gain
+                    } else {
+                        Vec2::ZERO
+                    };
+                    (signal, signal_gradient)
+                }
+            };
+            value += amplitude * octave_value;
+            gradient += amplitude * octave_gradient;
+            frequency *= settings.lacunarity;
+            amplitude *= settings.persistence;
+        }
+        (value, gradient)
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        let total = self.total_amplitude();
+        let extent = self.source_extent();
+        match self.settings.kind {
+            FractalKind::Fbm | FractalKind::Eroded => {
+                self.source.range().start() * total..=self.source.range().end() * total
+            }
+            FractalKind::Billow => -total..=(2.0 * extent - 1.0) * total,
+            FractalKind::Turbulence => 0.0..=extent * total,
+            FractalKind::Ridged => {
+                let offset = self.settings.offset;
+                let crest = offset.abs().max((extent - offset).abs());
+                0.0..=crest.powi(2) * total
+            }
+        }
+    }
+}
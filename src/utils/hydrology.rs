@@ -0,0 +1,199 @@
+use ndarray::Array2;
+use std::{cmp::Ordering, collections::BinaryHeap, f32::consts::SQRT_2};
+
+use crate::prelude::*;
+
+/// How water drains across a height map, from every cell to the map border.
+pub struct Drainage {
+    /// Height map with every depression filled up to the height at which it spills.
+    pub filled: Array2<f32>,
+    /// Cell each cell drains into, or `None` for border cells which drain off the map.
+    pub receivers: Array2<Option<(usize, usize)>>,
+    /// All cells, ordered so that every cell comes after the cell it drains into.
+    pub order: Vec<(usize, usize)>,
+}
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+impl Drainage {
+    /// Fill depressions with a priority-flood from the map border, then pick D8 flow directions.
+    /// Cells on slopes drain to their steepest downhill neighbour;
+    /// cells on flats or in filled depressions drain back along the path the flood reached them by.
+    pub fn new(height_map: &Array2<f32>) -> Self {
+        Self::with_progress(height_map, &GenerationProgress::default())
+            .expect("Progress nobody else holds is never cancelled")
+    }
+
+    /// As `new`, reporting how much of the map the flood has reached to `progress`,
+    /// and returning `None` if it is cancelled first.
+    pub fn with_progress(height_map: &Array2<f32>, progress: &GenerationProgress) -> Option<Self> {
+        // Number of cells flooded between progress reports
+        const PROGRESS_INTERVAL: usize = 1 << 16;
+
+        let (height, width) = height_map.dim();
+        let mut filled = height_map.clone();
+        let mut parents = Array2::from_elem(height_map.dim(), None);
+        let mut closed = Array2::from_elem(height_map.dim(), false);
+        let mut order = Vec::with_capacity(height_map.len());
+        let mut queue = BinaryHeap::new();
+        let mut pushed = 0;
+
+        for yi in 0..height {
+            for xi in 0..width {
+                if yi == 0 || xi == 0 || yi == height - 1 || xi == width - 1 {
+                    closed[(yi, xi)] = true;
+                    queue.push(FloodCell::new(filled[(yi, xi)], pushed, (yi, xi)));
+                    pushed += 1;
+                }
+            }
+        }
+
+        while let Some(cell) = queue.pop() {
+            if order.len() % PROGRESS_INTERVAL == 0
+                && !progress.report(order.len(), height_map.len())
+            {
+                return None;
+            }
+            order.push(cell.index);
+            for neighbour in neighbours(cell.index, (height, width)) {
+                if closed[neighbour] {
+                    continue;
+                }
+                closed[neighbour] = true;
+                filled[neighbour] = filled[neighbour].max(cell.elevation);
+                parents[neighbour] = Some(cell.index);
+                queue.push(FloodCell::new(filled[neighbour], pushed, neighbour));
+                pushed += 1;
+            }
+        }
+
+        // Flood parents drain across flats; override them wherever there is a true downhill slope
+        let mut receivers = parents;
+        for ((yi, xi), receiver) in receivers.indexed_iter_mut() {
+            if receiver.is_none() {
+                continue;
+            }
+            let elevation = filled[(yi, xi)];
+            let mut steepest = 0.0;
+            for neighbour in neighbours((yi, xi), (height, width)) {
+                let distance = if neighbour.0 != yi && neighbour.1 != xi {
+                    SQRT_2
+                } else {
+                    1.0
+                };
+                let slope = (elevation - filled[neighbour]) / distance;
+                if slope > steepest {
+                    steepest = slope;
+                    *receiver = Some(neighbour);
+                }
+            }
+        }
+
+        Some(Self {
+            filled,
+            receivers,
+            order,
+        })
+    }
+
+    /// Number of cells draining through each cell, including itself.
+    pub fn flow_accumulation(&self) -> Array2<f32> {
+        let mut accumulation = Array2::ones(self.filled.dim());
+        for &index in self.order.iter().rev() {
+            if let Some(receiver) = self.receivers[index] {
+                accumulation[receiver] += accumulation[index];
+            }
+        }
+        accumulation
+    }
+}
+
+/// Cells below sea level which are connected to the map border, as opposed to inland water.
+pub fn ocean_mask(height_map: &Array2<f32>, sea_level: f32) -> Array2<bool> {
+    let (height, width) = height_map.dim();
+    let mut ocean = Array2::from_elem(height_map.dim(), false);
+    let mut stack = Vec::new();
+    for yi in 0..height {
+        for xi in 0..width {
+            let is_border = yi == 0 || xi == 0 || yi == height - 1 || xi == width - 1;
+            if is_border && height_map[(yi, xi)] < sea_level {
+                ocean[(yi, xi)] = true;
+                stack.push((yi, xi));
+            }
+        }
+    }
+    while let Some(index) = stack.pop() {
+        for neighbour in neighbours(index, (height, width)) {
+            if !ocean[neighbour] && height_map[neighbour] < sea_level {
+                ocean[neighbour] = true;
+                stack.push(neighbour);
+            }
+        }
+    }
+    ocean
+}
+
+/// Indices of the up to eight cells surrounding a cell.
+pub fn neighbours(
+    (yi, xi): (usize, usize),
+    (height, width): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    NEIGHBOURS.iter().filter_map(move |(dy, dx)| {
+        let y = yi as isize + dy;
+        let x = xi as isize + dx;
+        if y < 0 || x < 0 || y >= height as isize || x >= width as isize {
+            None
+        } else {
+            Some((y as usize, x as usize))
+        }
+    })
+}
+
+/// Entry in the priority-flood queue, popped lowest first, and oldest first among equal elevations.
+struct FloodCell {
+    elevation: f32,
+    pushed: usize,
+    index: (usize, usize),
+}
+
+impl FloodCell {
+    fn new(elevation: f32, pushed: usize, index: (usize, usize)) -> Self {
+        Self {
+            elevation,
+            pushed,
+            index,
+        }
+    }
+}
+
+impl Ord for FloodCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .elevation
+            .total_cmp(&self.elevation)
+            .then_with(|| other.pushed.cmp(&self.pushed))
+    }
+}
+
+impl PartialOrd for FloodCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for FloodCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FloodCell {}
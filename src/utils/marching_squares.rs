@@ -0,0 +1,124 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use std::collections::{HashMap, VecDeque};
+
+/// Crossing point of the contour with a grid edge.
+/// Horizontal edges join `(y, x)` to `(y, x + 1)`, vertical edges join `(y, x)` to `(y + 1, x)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Edge {
+    Horizontal(usize, usize),
+    Vertical(usize, usize),
+}
+
+/// Trace the contour lines where the height map crosses `level`, using marching squares.
+/// Points are in cell coordinates, `x` along rows and `y` down columns.
+/// Closed contours repeat their first point at the end.
+pub fn contour_lines(height_map: &Array2<f32>, level: f32) -> Vec<Vec<Vec2>> {
+    let (height, width) = height_map.dim();
+    if width < 2 || height < 2 {
+        return Vec::new();
+    }
+
+    let point = |edge: Edge| -> Vec2 {
+        let ((y0, x0), (y1, x1)) = match edge {
+            Edge::Horizontal(y, x) => ((y, x), (y, x + 1)),
+            Edge::Vertical(y, x) => ((y, x), (y + 1, x)),
+        };
+        let (h0, h1) = (height_map[(y0, x0)], height_map[(y1, x1)]);
+        let t = if h1 != h0 {
+            (level - h0) / (h1 - h0)
+        } else {
+            0.5
+        };
+        vec2(x0 as f32, y0 as f32).lerp(vec2(x1 as f32, y1 as f32), t.clamp(0.0, 1.0))
+    };
+
+    // Segments of contour, each joining the two edges of a cell that it crosses
+    let mut segments: Vec<(Edge, Edge)> = Vec::new();
+    for yi in 0..height - 1 {
+        for xi in 0..width - 1 {
+            let top_left = height_map[(yi, xi)] >= level;
+            let top_right = height_map[(yi, xi + 1)] >= level;
+            let bottom_right = height_map[(yi + 1, xi + 1)] >= level;
+            let bottom_left = height_map[(yi + 1, xi)] >= level;
+            let case = (top_left as u8) << 3
+                | (top_right as u8) << 2
+                | (bottom_right as u8) << 1
+                | bottom_left as u8;
+
+            let top = Edge::Horizontal(yi, xi);
+            let bottom = Edge::Horizontal(yi + 1, xi);
+            let left = Edge::Vertical(yi, xi);
+            let right = Edge::Vertical(yi, xi + 1);
+
+            match case {
+                0 | 15 => {}
+                1 | 14 => segments.push((left, bottom)),
+                2 | 13 => segments.push((bottom, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((top, right)),
+                6 | 9 => segments.push((top, bottom)),
+                7 | 8 => segments.push((left, top)),
+                5 | 10 => {
+                    // Saddle, resolved by the average height of the cell
+                    let centre = (height_map[(yi, xi)]
+                        + height_map[(yi, xi + 1)]
+                        + height_map[(yi + 1, xi + 1)]
+                        + height_map[(yi + 1, xi)])
+                        * 0.25
+                        >= level;
+                    if (case == 5) == centre {
+                        segments.push((left, top));
+                        segments.push((bottom, right));
+                    } else {
+                        segments.push((left, bottom));
+                        segments.push((top, right));
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // Chain segments sharing an edge into polylines
+    let mut touching: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (index, (a, b)) in segments.iter().enumerate() {
+        touching.entry(*a).or_default().push(index);
+        touching.entry(*b).or_default().push(index);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut lines = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let mut chain = VecDeque::from([segments[start].0, segments[start].1]);
+
+        // Extend forwards from the end, then backwards from the start
+        for forwards in [true, false] {
+            loop {
+                let end = if forwards {
+                    chain[chain.len() - 1]
+                } else {
+                    chain[0]
+                };
+                let next = touching[&end].iter().copied().find(|&index| !used[index]);
+                let Some(next) = next else {
+                    break;
+                };
+                used[next] = true;
+                let (a, b) = segments[next];
+                let other = if a == end { b } else { a };
+                if forwards {
+                    chain.push_back(other);
+                } else {
+                    chain.push_front(other);
+                }
+            }
+        }
+
+        lines.push(chain.into_iter().map(point).collect());
+    }
+    lines
+}
@@ -1,3 +1,29 @@
+mod components;
+mod domain_warp;
+mod fractal;
+mod hydrology;
+mod marching_squares;
+mod noise_source;
+mod parallel;
 mod perlin_noise;
+mod perlin_noise_nd;
+mod permutation;
+pub mod portable;
+mod shadows;
+mod simplex_noise;
+mod value_noise;
+mod worley_noise;
 
-pub use perlin_noise::PerlinNoise;
+pub use components::label_components;
+pub use domain_warp::DomainWarp;
+pub use fractal::{Fractal, FractalKind, FractalSettings};
+pub use hydrology::{neighbours, ocean_mask, Drainage};
+pub use marching_squares::contour_lines;
+pub use noise_source::{central_differences, LayeredNoise, NoiseSource};
+pub use parallel::par_map_indexed;
+pub use perlin_noise::{PerlinLattice, PerlinLayer, PerlinNoise};
+pub use perlin_noise_nd::{AnimatedNoise, PerlinNoise3d, PerlinNoise4d, TorusNoise};
+pub use shadows::{ambient_occlusion, directional_light_visibility, point_light_visibility};
+pub use simplex_noise::{OpenSimplex2Noise, SimplexNoise};
+pub use value_noise::ValueNoise;
+pub use worley_noise::{DistanceMetric, WorleyFeature, WorleyNoise, WorleySample};
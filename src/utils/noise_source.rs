@@ -0,0 +1,86 @@
+use bevy::{math::vec2, prelude::*};
+use std::ops::RangeInclusive;
+
+/// A 2D noise function.
+pub trait NoiseSource: Send + Sync {
+    /// Noise value at a position.
+    fn sample(&self, position: Vec2) -> f32;
+
+    /// Bounds of the values `sample` can return.
+    fn range(&self) -> RangeInclusive<f32>;
+
+    /// Noise value at a position, and its gradient with respect to the position.
+    /// Estimated by central differences unless the source provides an exact gradient.
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        central_differences(|position| self.sample(position), position)
+    }
+}
+
+/// Value of a function and an estimate of its gradient from samples either side of the position.
+pub fn central_differences(function: impl Fn(Vec2) -> f32, position: Vec2) -> (f32, Vec2) {
+    const STEP: f32 = 1.0e-4;
+    let dx = function(position + vec2(STEP, 0.0)) - function(position - vec2(STEP, 0.0));
+    let dy = function(position + vec2(0.0, STEP)) - function(position - vec2(0.0, STEP));
+    (function(position), vec2(dx, dy) / (2.0 * STEP))
+}
+
+/// Weighted sum of noise sources, each stretched to a different frequency.
+/// Positions are fractions of the map, so a frequency of `(3, 3)` fits three noise cells across it.
+pub struct LayeredNoise {
+    pub layers: Vec<(Box<dyn NoiseSource>, Vec2, f32)>,
+}
+
+impl LayeredNoise {
+    /// Build one source per layer from `source`, which is given a different seed for each layer.
+    pub fn new<S: NoiseSource + 'static>(
+        seed: u64,
+        layers: &[(Vec2, f32)],
+        source: impl Fn(u64) -> S,
+    ) -> Self {
+        let layers = layers
+            .iter()
+            .enumerate()
+            .map(|(index, (frequency, weight))| {
+                let source: Box<dyn NoiseSource> =
+                    Box::new(source(seed.wrapping_add(index as u64)));
+                (source, *frequency, *weight)
+            })
+            .collect();
+        Self { layers }
+    }
+}
+
+impl NoiseSource for LayeredNoise {
+    fn sample(&self, position: Vec2) -> f32 {
+        self.layers
+            .iter()
+            .map(|(source, frequency, weight)| source.sample(position * *frequency) * weight)
+            .sum()
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        self.layers.iter().fold(
+            (0.0, Vec2::ZERO),
+            |(value, gradient), (source, frequency, weight)| {
+                let (layer_value, layer_gradient) =
+                    source.sample_with_gradient(position * *frequency);
+                (
+                    value + layer_value * weight,
+                    gradient + layer_gradient * *frequency * *weight,
+                )
+            },
+        )
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        self.layers
+            .iter()
+            .fold(0.0..=0.0, |range, (source, _frequency, weight)| {
+                let (low, high) = (
+                    source.range().start() * weight,
+                    source.range().end() * weight,
+                );
+                range.start() + low.min(high)..=range.end() + low.max(high)
+            })
+    }
+}
@@ -0,0 +1,18 @@
+use ndarray::{parallel::prelude::*, Array2, Axis};
+
+/// Replace every cell with a function of its index and value, splitting the rows across threads.
+/// Each cell is computed on its own, so the result is the same whatever the number of threads.
+pub fn par_map_indexed<T: Send + Sync>(
+    array: &mut Array2<T>,
+    function: impl Fn((usize, usize), &T) -> T + Sync,
+) {
+    array
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(yi, mut row)| {
+            for (xi, value) in row.iter_mut().enumerate() {
+                *value = function((yi, xi), value);
+            }
+        });
+}
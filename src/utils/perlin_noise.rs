@@ -1,10 +1,47 @@
 use bevy::{math::vec2, prelude::*};
 use ndarray::Array2;
 use rand::prelude::*;
-use std::f32::consts::{SQRT_2, TAU};
+use rand_chacha::ChaCha8Rng;
+use std::{f32::consts::SQRT_2, ops::RangeInclusive};
 
+use crate::prelude::*;
+
+/// Perlin gradient noise, summed over layers of gradient lattices at different frequencies.
 pub struct PerlinNoise {
-    pub vector_layers: Vec<(Array2<Vec2>, f32)>,
+    pub seed: u64,
+    pub layers: Vec<PerlinLayer>,
+}
+
+/// One octave of `PerlinNoise`.
+pub struct PerlinLayer {
+    /// Lattice cells per unit of position along each axis.
+    pub frequency: Vec2,
+    pub weight: f32,
+    pub lattice: PerlinLattice,
+}
+
+/// Where the gradients of a `PerlinLayer` come from.
+pub enum PerlinLattice {
+    /// Stored gradients, repeating every grid's size in lattice cells.
+    Periodic(Array2<Vec2>),
+    /// Gradients hashed from the lattice coordinates, never repeating.
+    Infinite { seed: u64 },
+}
+
+impl PerlinLayer {
+    /// Gradient at a lattice point, which may lie at negative coordinates.
+    pub fn gradient(&self, x: i32, y: i32) -> Vec2 {
+        match &self.lattice {
+            PerlinLattice::Periodic(vectors) => {
+                let (height, width) = vectors.dim();
+                vectors[(
+                    y.rem_euclid(height as i32) as usize,
+                    x.rem_euclid(width as i32) as usize,
+                )]
+            }
+            PerlinLattice::Infinite { seed } => SQRT_2 * hashed_direction(*seed, x, y),
+        }
+    }
 }
 
 // impl Default for PerlinNoise {
@@ -35,63 +72,95 @@ pub struct PerlinNoise {
 // }
 
 impl PerlinNoise {
-    pub fn new(layers: Vec<((usize, usize), f32)>) -> Self {
-        let vector_layers = layers
-            .iter()
-            .map(|((width, height), weight)| {
-                (Array2::from_elem((*height, *width), Vec2::ZERO), *weight)
+    /// Layers of the given frequencies and weights, all repeating every `period` units of position.
+    /// Each frequency is rounded to fit a whole number of lattice cells into the period, so the tiling is seamless.
+    pub fn periodic(seed: u64, layers: Vec<(Vec2, f32)>, period: Vec2) -> Self {
+        let layers = layers
+            .into_iter()
+            .map(|(frequency, weight)| {
+                let cells = (frequency * period).round().max(Vec2::ONE);
+                PerlinLayer {
+                    frequency: cells / period,
+                    weight,
+                    lattice: PerlinLattice::Periodic(Array2::from_elem(
+                        (cells.y as usize, cells.x as usize),
+                        Vec2::ZERO,
+                    )),
+                }
+            })
+            .collect();
+        Self::with_layers(seed, layers)
+    }
+
+    /// Layers of the given frequencies and weights which never repeat, for sampling anywhere in the world.
+    pub fn infinite(seed: u64, layers: Vec<(Vec2, f32)>) -> Self {
+        let layers = layers
+            .into_iter()
+            .map(|(frequency, weight)| PerlinLayer {
+                frequency,
+                weight,
+                lattice: PerlinLattice::Infinite { seed },
             })
             .collect();
+        Self::with_layers(seed, layers)
+    }
 
-        let mut perlin_noise = Self { vector_layers };
-        perlin_noise.randomise();
+    fn with_layers(seed: u64, layers: Vec<PerlinLayer>) -> Self {
+        let mut perlin_noise = Self { seed, layers };
+        perlin_noise.randomise(seed);
 
-        return perlin_noise;
+        perlin_noise
     }
 
     /// Randomly orientate all vectors in all layers.
-    pub fn randomise(&mut self) {
-        let mut rng = thread_rng();
-        for (vectors, _weight) in &mut self.vector_layers {
-            let width = vectors.ncols();
-            let height = vectors.nrows();
-            for yi in 0..height {
-                for xi in 0..width {
-                    let theta = rng.gen_range(0.0..TAU);
-                    let x = SQRT_2 * theta.cos();
-                    let y = SQRT_2 * theta.sin();
-                    vectors[(yi, xi)] = vec2(x, y);
+    /// The same seed always produces the same vectors, on any machine.
+    pub fn randomise(&mut self, seed: u64) {
+        self.seed = seed;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            match &mut layer.lattice {
+                PerlinLattice::Periodic(vectors) => {
+                    let width = vectors.ncols();
+                    let height = vectors.nrows();
+                    for yi in 0..height {
+                        for xi in 0..width {
+                            vectors[(yi, xi)] = SQRT_2 * Self::random_direction(&mut rng);
+                        }
+                    }
+                }
+                PerlinLattice::Infinite { seed: layer_seed } => {
+                    *layer_seed = seed.wrapping_add(index as u64);
                 }
             }
         }
     }
 
-    /// Returns a value between 0 and 1.
-    pub fn sample(&self, position: Vec2) -> f32 {
-        let mut value = 0.0;
-        for (vectors, weight) in &self.vector_layers {
-            value += self.sample_layer(vectors, position) * weight;
+    /// Uniformly distributed unit vector.
+    /// Uses rejection sampling rather than trigonometry, as `sin` and `cos` are not bit-identical across platforms.
+    fn random_direction(rng: &mut impl Rng) -> Vec2 {
+        loop {
+            let v = vec2(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0));
+            let length_squared = v.length_squared();
+            if length_squared > 1.0e-6 && length_squared <= 1.0 {
+                return v / length_squared.sqrt();
+            }
         }
-        value
     }
 
-    /// Returns a value between 0 and 1.
-    pub fn sample_layer(&self, layer: &Array2<Vec2>, position: Vec2) -> f32 {
-        let height = layer.nrows() as i32;
-        let width = layer.ncols() as i32;
-
-        let left = (width as f32 * position.x).floor() as i32 % width;
-        let right = (left + 1) % width;
-        let top = (height as f32 * position.y).floor() as i32 % height;
-        let bottom = (top + 1) % height;
+    /// Returns a value between -1 and 1.
+    pub fn sample_layer(&self, layer: &PerlinLayer, position: Vec2) -> f32 {
+        let position = position * layer.frequency;
+        let left = position.x.floor() as i32;
+        let top = position.y.floor() as i32;
 
-        let top_left = layer[(top as usize, left as usize)];
-        let top_right = layer[(top as usize, right as usize)];
-        let bottom_left = layer[(bottom as usize, left as usize)];
-        let bottom_right = layer[(bottom as usize, right as usize)];
+        let top_left = layer.gradient(left, top);
+        let top_right = layer.gradient(left + 1, top);
+        let bottom_left = layer.gradient(left, top + 1);
+        let bottom_right = layer.gradient(left + 1, top + 1);
 
-        let xf = (width as f32 * position.x).fract();
-        let yf = (height as f32 * position.y).fract();
+        // Measured from the cell's left and top edges, so also positive for negative positions
+        let xf = position.x - position.x.floor();
+        let yf = position.y - position.y.floor();
 
         // Correct relative position vectors for gradients
         let top_left_gradient = top_left.dot(vec2(xf, yf));
@@ -105,6 +174,44 @@ impl PerlinNoise {
         Self::lerp(top_gradient, bottom_gradient, yf)
     }
 
+    /// Returns a value between -1 and 1, and its gradient with respect to the position.
+    pub fn sample_layer_with_gradient(&self, layer: &PerlinLayer, position: Vec2) -> (f32, Vec2) {
+        let position = position * layer.frequency;
+        let left = position.x.floor() as i32;
+        let top = position.y.floor() as i32;
+
+        let top_left = layer.gradient(left, top);
+        let top_right = layer.gradient(left + 1, top);
+        let bottom_left = layer.gradient(left, top + 1);
+        let bottom_right = layer.gradient(left + 1, top + 1);
+
+        let xf = position.x - position.x.floor();
+        let yf = position.y - position.y.floor();
+
+        let a = top_left.dot(vec2(xf, yf));
+        let b = top_right.dot(vec2(xf - 1.0, yf));
+        let c = bottom_left.dot(vec2(xf, yf - 1.0));
+        let d = bottom_right.dot(vec2(xf - 1.0, yf - 1.0));
+
+        // Bilinear blend of the corners, `a + sx (b - a) + sy (c - a) + sx sy (a - b - c + d)`,
+        // differentiated through both the corner values and the smoothstep weights
+        let (sx, sy) = (Self::smoothstep(xf), Self::smoothstep(yf));
+        let (dsx, dsy) = (
+            Self::smoothstep_derivative(xf),
+            Self::smoothstep_derivative(yf),
+        );
+        let k = a - b - c + d;
+        let value = a + sx * (b - a) + sy * (c - a) + sx * sy * k;
+        let gradient = top_left
+            + sx * (top_right - top_left)
+            + sy * (bottom_left - top_left)
+            + sx * sy * (top_left - top_right - bottom_left + bottom_right)
+            + vec2(dsx * (b - a + sy * k), dsy * (c - a + sx * k));
+
+        // Lattice coordinates change `frequency` times faster than the position
+        (value, gradient * layer.frequency)
+    }
+
     fn lerp(a: f32, b: f32, t: f32) -> f32 {
         let t = Self::smoothstep(t);
         a * (1.0 - t) + b * t
@@ -113,4 +220,61 @@ impl PerlinNoise {
     fn smoothstep(t: f32) -> f32 {
         t * t * (3.0 - 2.0 * t)
     }
+
+    fn smoothstep_derivative(t: f32) -> f32 {
+        6.0 * t * (1.0 - t)
+    }
+}
+
+impl NoiseSource for PerlinNoise {
+    /// Weighted sum of all layers.
+    fn sample(&self, position: Vec2) -> f32 {
+        let mut value = 0.0;
+        for layer in &self.layers {
+            value += self.sample_layer(layer, position) * layer.weight;
+        }
+        value
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let mut value = 0.0;
+        let mut gradient = Vec2::ZERO;
+        for layer in &self.layers {
+            let (layer_value, layer_gradient) = self.sample_layer_with_gradient(layer, position);
+            value += layer_value * layer.weight;
+            gradient += layer_gradient * layer.weight;
+        }
+        (value, gradient)
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        let total: f32 = self.layers.iter().map(|layer| layer.weight.abs()).sum();
+        -total..=total
+    }
+}
+
+/// Uniformly distributed unit vector for a lattice point, by rejection sampling successive hashes of it.
+fn hashed_direction(seed: u64, x: i32, y: i32) -> Vec2 {
+    let mut attempt: u64 = 0;
+    loop {
+        let hash = mix(seed
+            ^ mix((x as u32 as u64) << 32 | y as u32 as u64)
+            ^ attempt.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        let u = (hash >> 40) as f32 / (1u64 << 24) as f32;
+        let v = (hash >> 16 & 0xFF_FFFF) as f32 / (1u64 << 24) as f32;
+        let direction = vec2(u, v) * 2.0 - 1.0;
+        let length_squared = direction.length_squared();
+        if length_squared > 1.0e-6 && length_squared <= 1.0 {
+            return direction / length_squared.sqrt();
+        }
+        attempt += 1;
+    }
+}
+
+/// SplitMix64 finaliser.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
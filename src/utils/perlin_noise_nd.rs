@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use std::{f32::consts::TAU, ops::RangeInclusive};
+
+use super::permutation::PermutationTable;
+use crate::prelude::*;
+
+/// Gradients pointing from the centre of a cube to the middles of its 12 edges.
+const GRADIENTS_3D: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// Gradients pointing from the centre of a tesseract to the middles of its 32 edges.
+const GRADIENTS_4D: [[f32; 4]; 32] = {
+    let mut gradients = [[0.0; 4]; 32];
+    let mut index = 0;
+    while index < 32 {
+        // One axis is zero, the other three take every combination of signs
+        let zero_axis = index / 8;
+        let signs = index % 8;
+        let mut axis = 0;
+        let mut bit = 0;
+        while axis < 4 {
+            if axis != zero_axis {
+                gradients[index][axis] = if signs >> bit & 1 == 0 { 1.0 } else { -1.0 };
+                bit += 1;
+            }
+            axis += 1;
+        }
+        index += 1;
+    }
+    gradients
+};
+
+/// 3D Perlin noise on an infinite lattice, for animating 2D noise by moving through the third axis.
+pub struct PerlinNoise3d {
+    pub seed: u64,
+    table: PermutationTable,
+}
+
+impl PerlinNoise3d {
+    /// Scale bringing the peaks to 1.
+    const SCALE: f32 = 0.96;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            table: PermutationTable::new(seed),
+        }
+    }
+
+    /// Returns a value between -1 and 1, with lattice points one unit apart.
+    pub fn sample(&self, position: Vec3) -> f32 {
+        sample_lattice(&self.table, position.to_array(), |hash| {
+            GRADIENTS_3D[hash % GRADIENTS_3D.len()]
+        }) * Self::SCALE
+    }
+}
+
+/// 4D Perlin noise on an infinite lattice.
+/// Two circles through 4D space make a torus, which `TorusNoise` uses to tile 2D noise seamlessly.
+pub struct PerlinNoise4d {
+    pub seed: u64,
+    table: PermutationTable,
+}
+
+impl PerlinNoise4d {
+    /// Scale bringing the peaks to 1.
+    const SCALE: f32 = 0.86;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            table: PermutationTable::new(seed),
+        }
+    }
+
+    /// Returns a value between -1 and 1, with lattice points one unit apart.
+    pub fn sample(&self, position: Vec4) -> f32 {
+        sample_lattice(&self.table, position.to_array(), |hash| {
+            GRADIENTS_4D[hash % GRADIENTS_4D.len()]
+        }) * Self::SCALE
+    }
+}
+
+/// Perlin noise in any number of dimensions, blending the ramps from every corner of the lattice cell.
+fn sample_lattice<const N: usize>(
+    table: &PermutationTable,
+    position: [f32; N],
+    gradient: impl Fn(usize) -> [f32; N],
+) -> f32 {
+    let cell = position.map(|coordinate| coordinate.floor());
+    let mut fraction = [0.0; N];
+    let mut fade = [0.0; N];
+    for axis in 0..N {
+        fraction[axis] = position[axis] - cell[axis];
+        fade[axis] = smootherstep(fraction[axis]);
+    }
+
+    let mut value = 0.0;
+    let mut corner = [0; N];
+    for corner_bits in 0..1 << N {
+        let mut weight = 1.0;
+        let mut ramp = 0.0;
+        let mut delta = [0.0; N];
+        for axis in 0..N {
+            let step = corner_bits >> axis & 1;
+            corner[axis] = cell[axis] as i32 + step;
+            delta[axis] = fraction[axis] - step as f32;
+            weight *= if step == 1 {
+                fade[axis]
+            } else {
+                1.0 - fade[axis]
+            };
+        }
+        let gradient = gradient(table.hash_point(&corner));
+        for axis in 0..N {
+            ramp += gradient[axis] * delta[axis];
+        }
+        value += weight * ramp;
+    }
+    value
+}
+
+/// Quintic easing, with zero first and second derivatives at both ends to hide the lattice.
+fn smootherstep(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// 2D slice through 3D Perlin noise at a moment in time, which changes smoothly as `time` advances.
+pub struct AnimatedNoise {
+    pub noise: PerlinNoise3d,
+    /// Lattice cells across the map.
+    pub frequency: Vec2,
+    /// Position along the third axis, in lattice cells.
+    pub time: f32,
+}
+
+impl AnimatedNoise {
+    pub fn new(seed: u64, frequency: Vec2) -> Self {
+        Self {
+            noise: PerlinNoise3d::new(seed),
+            frequency,
+            time: 0.0,
+        }
+    }
+
+    pub fn at_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
+}
+
+impl NoiseSource for AnimatedNoise {
+    /// Positions are fractions of the map.
+    fn sample(&self, position: Vec2) -> f32 {
+        self.noise
+            .sample((position * self.frequency).extend(self.time))
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        -1.0..=1.0
+    }
+}
+
+/// 2D noise which tiles seamlessly across the unit square, by sampling 4D Perlin noise around a torus.
+/// Wrapping the map's `x` and `y` onto two separate circles avoids both the visible lattice of `PerlinNoise`
+/// and the stretching of a 3D cylinder.
+pub struct TorusNoise {
+    pub noise: PerlinNoise4d,
+    /// Lattice cells around each circle, so roughly the number of noise features across the map.
+    pub frequency: Vec2,
+}
+
+impl TorusNoise {
+    pub fn new(seed: u64, frequency: Vec2) -> Self {
+        Self {
+            noise: PerlinNoise4d::new(seed),
+            frequency,
+        }
+    }
+
+    /// Point on the torus for a position, repeating every whole unit along each axis.
+    pub fn torus_point(&self, position: Vec2) -> Vec4 {
+        let radius = self.frequency / TAU;
+        let (sin_x, cos_x) = portable::sin_cos(position.x * TAU);
+        let (sin_y, cos_y) = portable::sin_cos(position.y * TAU);
+        Vec4::new(
+            cos_x * radius.x,
+            sin_x * radius.x,
+            cos_y * radius.y,
+            sin_y * radius.y,
+        )
+    }
+}
+
+impl NoiseSource for TorusNoise {
+    /// Positions are fractions of the map.
+    fn sample(&self, position: Vec2) -> f32 {
+        self.noise.sample(self.torus_point(position))
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        -1.0..=1.0
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+/// Unit gradients 15 degrees apart, offset from the axes so none line up with the lattice.
+pub const GRADIENTS: [Vec2; 24] = [
+    Vec2::new(0.130_526_19, 0.991_444_9),
+    Vec2::new(0.382_683_43, 0.923_879_5),
+    Vec2::new(0.608_761_4, 0.793_353_3),
+    Vec2::new(0.793_353_3, 0.608_761_4),
+    Vec2::new(0.923_879_5, 0.382_683_43),
+    Vec2::new(0.991_444_9, 0.130_526_19),
+    Vec2::new(0.991_444_9, -0.130_526_19),
+    Vec2::new(0.923_879_5, -0.382_683_43),
+    Vec2::new(0.793_353_3, -0.608_761_4),
+    Vec2::new(0.608_761_4, -0.793_353_3),
+    Vec2::new(0.382_683_43, -0.923_879_5),
+    Vec2::new(0.130_526_19, -0.991_444_9),
+    Vec2::new(-0.130_526_19, -0.991_444_9),
+    Vec2::new(-0.382_683_43, -0.923_879_5),
+    Vec2::new(-0.608_761_4, -0.793_353_3),
+    Vec2::new(-0.793_353_3, -0.608_761_4),
+    Vec2::new(-0.923_879_5, -0.382_683_43),
+    Vec2::new(-0.991_444_9, -0.130_526_19),
+    Vec2::new(-0.991_444_9, 0.130_526_19),
+    Vec2::new(-0.923_879_5, 0.382_683_43),
+    Vec2::new(-0.793_353_3, 0.608_761_4),
+    Vec2::new(-0.608_761_4, 0.793_353_3),
+    Vec2::new(-0.382_683_43, 0.923_879_5),
+    Vec2::new(-0.130_526_19, 0.991_444_9),
+];
+
+/// Seeded hash of integer lattice points, repeating every 256 points along each axis.
+pub struct PermutationTable {
+    permutation: [u8; 256],
+}
+
+impl PermutationTable {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut permutation = [0; 256];
+        for (index, value) in permutation.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+        permutation.shuffle(&mut rng);
+        Self { permutation }
+    }
+
+    /// Hash of a lattice point, between 0 and 255.
+    /// Negative coordinates wrap around like positive ones.
+    pub fn hash(&self, x: i32, y: i32) -> usize {
+        let column = self.permutation[(x & 255) as usize] as usize;
+        self.permutation[(column + (y & 255) as usize) & 255] as usize
+    }
+
+    /// Hash of a lattice point with any number of coordinates, between 0 and 255.
+    /// Agrees with `hash` for two coordinates.
+    pub fn hash_point(&self, coordinates: &[i32]) -> usize {
+        coordinates.iter().fold(0, |hash, coordinate| {
+            self.permutation[(hash + (coordinate & 255) as usize) & 255] as usize
+        })
+    }
+
+    /// Gradient at a lattice point.
+    pub fn gradient(&self, x: i32, y: i32) -> Vec2 {
+        GRADIENTS[self.hash(x, y) % GRADIENTS.len()]
+    }
+}
@@ -0,0 +1,151 @@
+//! Transcendental functions for seeded generation, built only from IEEE 754 arithmetic and square roots.
+//! The standard library calls into the platform's maths library, whose results can differ in the last bit
+//! between platforms, so the same seed would not always generate exactly the same terrain.
+//! These work in `f64` and round once at the end, to within an ulp or so of the correctly rounded `f32`.
+
+use bevy::math::Vec2;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, LN_2};
+
+/// Sine and cosine of an angle in radians.
+pub fn sin_cos(angle: f32) -> (f32, f32) {
+    let (sin, cos) = sin_cos_f64(angle as f64);
+    (sin as f32, cos as f32)
+}
+
+/// Unit vector at an angle in radians anticlockwise from the x axis, as `Vec2::from_angle`.
+pub fn from_angle(angle: f32) -> Vec2 {
+    let (sin, cos) = sin_cos(angle);
+    Vec2::new(cos, sin)
+}
+
+pub fn sin(angle: f32) -> f32 {
+    sin_cos(angle).0
+}
+
+pub fn tan(angle: f32) -> f32 {
+    let (sin, cos) = sin_cos_f64(angle as f64);
+    (sin / cos) as f32
+}
+
+pub fn exp(x: f32) -> f32 {
+    exp_f64(x as f64) as f32
+}
+
+/// Natural logarithm, which is NaN below zero.
+pub fn ln(x: f32) -> f32 {
+    ln_f64(x as f64) as f32
+}
+
+/// `x` raised to the power `y`, for `x` of at least zero.
+pub fn powf(x: f32, y: f32) -> f32 {
+    if x == 0.0 {
+        return if y > 0.0 { 0.0 } else { f32::INFINITY };
+    }
+    exp_f64(y as f64 * ln_f64(x as f64)) as f32
+}
+
+/// Angle of the point `(x, y)` from the x axis, in radians from -PI to PI, as `f32::atan2`.
+pub fn atan2(y: f32, x: f32) -> f32 {
+    let (y, x) = (y as f64, x as f64);
+    if x == 0.0 && y == 0.0 {
+        return 0.0;
+    }
+    let angle = if x.abs() >= y.abs() {
+        atan_f64(y.abs() / x.abs())
+    } else {
+        FRAC_PI_2 - atan_f64(x.abs() / y.abs())
+    };
+    let angle = if x < 0.0 {
+        std::f64::consts::PI - angle
+    } else {
+        angle
+    };
+    (if y < 0.0 { -angle } else { angle }) as f32
+}
+
+fn sin_cos_f64(angle: f64) -> (f64, f64) {
+    // Reduce to within a quarter turn of zero, then use Taylor series
+    let quadrant = (angle / FRAC_PI_2).round();
+    let x = angle - quadrant * FRAC_PI_2;
+    let x2 = x * x;
+    let mut sin = 0.0;
+    let mut cos = 0.0;
+    for n in (1..=8).rev() {
+        let n = n as f64;
+        sin = (1.0 - sin) * x2 / ((2.0 * n) * (2.0 * n + 1.0));
+        cos = (1.0 - cos) * x2 / ((2.0 * n - 1.0) * (2.0 * n));
+    }
+    let (sin, cos) = (x * (1.0 - sin), 1.0 - cos);
+    match (quadrant as i64).rem_euclid(4) {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+fn exp_f64(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    // Beyond the range of f32 either way
+    if x > 89.0 {
+        return f64::INFINITY;
+    }
+    if x < -104.0 {
+        return 0.0;
+    }
+    // exp(x) = 2^k exp(r), with |r| at most half of ln 2
+    let k = (x / LN_2).round();
+    let r = x - k * LN_2;
+    let mut sum = 1.0;
+    for n in (1..=13).rev() {
+        sum = 1.0 + sum * r / n as f64;
+    }
+    sum * f64::from_bits(((k as i64 + 1023) as u64) << 52)
+}
+
+fn ln_f64(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x.is_infinite() {
+        return x;
+    }
+    // x = m 2^e with m within a factor of sqrt 2 of one. Inputs come from f32, so are never subnormal here.
+    let bits = x.to_bits();
+    let mut exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    if mantissa > std::f64::consts::SQRT_2 {
+        mantissa /= 2.0;
+        exponent += 1;
+    }
+    // ln m = 2 atanh(s), with s = (m - 1) / (m + 1)
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s2 = s * s;
+    let mut sum = 0.0;
+    for n in (0..=10).rev() {
+        sum = sum * s2 + 1.0 / (2 * n + 1) as f64;
+    }
+    2.0 * s * sum + exponent as f64 * LN_2
+}
+
+/// Arctangent of a value from zero to one.
+fn atan_f64(x: f64) -> f64 {
+    // atan x = PI / 4 + atan((x - 1) / (x + 1)), bringing x within tan(PI / 8) of zero
+    let (offset, x) = if x > std::f64::consts::SQRT_2 - 1.0 {
+        (FRAC_PI_4, (x - 1.0) / (x + 1.0))
+    } else {
+        (0.0, x)
+    };
+    let x2 = x * x;
+    let mut sum = 0.0;
+    for n in (0..=18).rev() {
+        let term = 1.0 / (2 * n + 1) as f64;
+        sum = if n % 2 == 0 { term } else { -term } + sum * x2;
+    }
+    offset + x * sum
+}
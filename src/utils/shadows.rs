@@ -0,0 +1,240 @@
+use bevy::prelude::*;
+use ndarray::Array2;
+
+use crate::prelude::*;
+
+/// Heights within this fraction of the penumbra of a shadow's edge still count as lit,
+/// so slopes do not shadow themselves.
+const SHADOW_BIAS: f32 = 0.2;
+
+/// Visibility of each cell from a point light, from 0 in full shadow to 1 fully lit.
+/// The light's position is in cells, and its height in the same units as the heights, which should be cells too
+/// for the shadows to match the relief the terrain is lit with.
+/// Cells up to `penumbra` below the edge of a shadow are partly lit, so shadows soften further from what casts them.
+///
+/// Works outwards from the light one square ring of cells at a time, carrying the steepest slope seen from the light
+/// so far along each ray, so the whole map costs a single pass rather than a ray march per cell.
+pub fn point_light_visibility(heights: &Array2<f32>, light: Vec3, penumbra: f32) -> Array2<f32> {
+    let (height, width) = heights.dim();
+    let size = IVec2::new(width as i32, height as i32);
+    let centre = light.truncate().floor().as_ivec2();
+    let in_map = |cell: IVec2| cell.cmpge(IVec2::ZERO).all() && cell.cmplt(size).all();
+
+    // Steepest slope, seen from the light, of any cell on the way from the light to each cell
+    let mut horizon = Array2::from_elem((height, width), f32::NEG_INFINITY);
+    let mut visibility = Array2::from_elem((height, width), 1.0);
+    let horizon_at = |horizon: &Array2<f32>, cell: IVec2| {
+        if in_map(cell) {
+            horizon[(cell.y as usize, cell.x as usize)]
+        } else {
+            f32::NEG_INFINITY
+        }
+    };
+
+    let mut visit = |cell: IVec2| {
+        let offset = cell - centre;
+        let ring = offset.abs().max_element();
+
+        // Horizon where the ray to the light crosses the next ring in, between two of its cells
+        let inward = if ring <= 1 {
+            f32::NEG_INFINITY
+        } else {
+            let scale = (ring - 1) as f32 / ring as f32;
+            let (a, b, t) = if offset.x.abs() == ring {
+                let y = offset.y as f32 * scale;
+                let x = offset.x.signum() * (ring - 1);
+                let low = y.floor() as i32;
+                (IVec2::new(x, low), IVec2::new(x, low + 1), y - low as f32)
+            } else {
+                let x = offset.x as f32 * scale;
+                let y = offset.y.signum() * (ring - 1);
+                let low = x.floor() as i32;
+                (IVec2::new(low, y), IVec2::new(low + 1, y), x - low as f32)
+            };
+            let (a, b) = (
+                horizon_at(&horizon, centre + a),
+                horizon_at(&horizon, centre + b),
+            );
+            if a.is_finite() && b.is_finite() {
+                a + (b - a) * t
+            } else {
+                a.max(b)
+            }
+        };
+
+        let index = (cell.y as usize, cell.x as usize);
+        let distance = (cell.as_vec2() - light.truncate()).length().max(1.0e-3);
+        let slope = (heights[index] - light.z) / distance;
+        if inward.is_finite() {
+            // Height of the shadow cast by the horizon, here
+            let shadow_height = light.z + inward * distance;
+            let depth = shadow_height - heights[index] - SHADOW_BIAS * penumbra;
+            visibility[index] = (1.0 - depth / penumbra.max(f32::EPSILON)).clamp(0.0, 1.0);
+        }
+        horizon[index] = slope.max(inward);
+    };
+
+    // Only rings which overlap the map, even if the light is outside it
+    let nearest = centre.clamp(IVec2::ZERO, size - 1);
+    let first_ring = (nearest - centre).abs().max_element();
+    let last_ring = [
+        IVec2::ZERO,
+        IVec2::new(size.x - 1, 0),
+        IVec2::new(0, size.y - 1),
+        size - 1,
+    ]
+    .into_iter()
+    .map(|corner| (corner - centre).abs().max_element())
+    .max()
+    .unwrap();
+    for ring in first_ring..=last_ring {
+        let (top, bottom) = (centre.y - ring, centre.y + ring);
+        for x in (centre.x - ring).max(0)..=(centre.x + ring).min(size.x - 1) {
+            for y in [top, bottom] {
+                if in_map(IVec2::new(x, y)) && (y == top || ring > 0) {
+                    visit(IVec2::new(x, y));
+                }
+            }
+        }
+        let (left, right) = (centre.x - ring, centre.x + ring);
+        for y in (top + 1).max(0)..=(bottom - 1).min(size.y - 1) {
+            for x in [left, right] {
+                if in_map(IVec2::new(x, y)) && (x == left || ring > 0) {
+                    visit(IVec2::new(x, y));
+                }
+            }
+        }
+    }
+
+    visibility
+}
+
+/// Visibility of each cell from a directional light such as the sun, from 0 in full shadow to 1 fully lit.
+/// The direction points towards the light, across the map in cells and up in the same units as the heights.
+/// Cells up to `penumbra` below the edge of a shadow are partly lit, as for `point_light_visibility`.
+///
+/// Sweeps across the map away from the light one row or column at a time, carrying the height of the highest
+/// shadow over each cell, so the whole map costs a single pass.
+pub fn directional_light_visibility(
+    heights: &Array2<f32>,
+    direction: Vec3,
+    penumbra: f32,
+) -> Array2<f32> {
+    let (height, width) = heights.dim();
+    let mut visibility = Array2::from_elem((height, width), 1.0);
+    let across = direction.truncate();
+    if across.length() <= f32::EPSILON {
+        // Overhead, so nothing casts a shadow
+        return visibility;
+    }
+    let rise = direction.z / across.length();
+
+    // Sweep lines of cells along whichever axis the light moves along fastest
+    let along_x = across.x.abs() >= across.y.abs();
+    let (lines, line_length, major, minor) = if along_x {
+        (width, height, across.x, across.y)
+    } else {
+        (height, width, across.y, across.x)
+    };
+    let index = |line: usize, position: usize| {
+        if along_x {
+            (position, line)
+        } else {
+            (line, position)
+        }
+    };
+    // Offset along the line, and distance, to where the ray to the light crosses the previous line
+    let shift = minor / major.abs();
+    let step = (1.0 + shift * shift).sqrt();
+
+    // Height of the highest shadow over each cell, or of the ground if that is higher
+    let mut shadow = Array2::from_elem((height, width), f32::NEG_INFINITY);
+    for count in 0..lines {
+        let line = if major > 0.0 {
+            lines - 1 - count
+        } else {
+            count
+        };
+        let previous = line as isize + major.signum() as isize;
+        for position in 0..line_length {
+            let incoming = if previous < 0 || previous >= lines as isize {
+                f32::NEG_INFINITY
+            } else {
+                let crossing = position as f32 + shift;
+                let low = crossing.floor();
+                let t = crossing - low;
+                let shadow_at = |position: isize| {
+                    if position < 0 || position >= line_length as isize {
+                        f32::NEG_INFINITY
+                    } else {
+                        shadow[index(previous as usize, position as usize)]
+                    }
+                };
+                let (a, b) = (shadow_at(low as isize), shadow_at(low as isize + 1));
+                let highest = if a.is_finite() && b.is_finite() {
+                    a + (b - a) * t
+                } else {
+                    a.max(b)
+                };
+                highest - rise * step
+            };
+
+            let index = index(line, position);
+            if incoming.is_finite() {
+                let depth = incoming - heights[index] - SHADOW_BIAS * penumbra;
+                visibility[index] = (1.0 - depth / penumbra.max(f32::EPSILON)).clamp(0.0, 1.0);
+            }
+            shadow[index] = heights[index].max(incoming);
+        }
+    }
+
+    visibility
+}
+
+/// Fraction of the sky visible from each cell, from 0 in a deep pit to 1 on open ground.
+/// `vertical_scale` is the number of cells spanned by one unit of height.
+///
+/// Finds the horizon in eight directions, out to `radius` cells at increasing spacing,
+/// and averages how much of each direction's sky it hides.
+pub fn ambient_occlusion(heights: &Array2<f32>, radius: usize, vertical_scale: f32) -> Array2<f32> {
+    let (height, width) = heights.dim();
+    let mut distances = Vec::new();
+    let mut distance = 1.0;
+    while distance <= radius as f32 {
+        distances.push(distance);
+        distance = (distance * 1.5).ceil();
+    }
+    // Cell offsets along each direction, nearest first
+    let rays: Vec<Vec<(IVec2, f32)>> = (0..8)
+        .map(|direction| {
+            let direction = Vec2::from_angle(direction as f32 * std::f32::consts::FRAC_PI_4);
+            distances
+                .iter()
+                .map(|&distance| ((direction * distance).round().as_ivec2(), distance))
+                .collect()
+        })
+        .collect();
+
+    let size = IVec2::new(width as i32, height as i32);
+    let mut occlusion = Array2::zeros((height, width));
+    par_map_indexed(&mut occlusion, |(yi, xi), _| {
+        let cell = IVec2::new(xi as i32, yi as i32);
+        let here = heights[(yi, xi)];
+        let mut hidden = 0.0;
+        for ray in &rays {
+            let mut horizon: f32 = 0.0;
+            for &(offset, distance) in ray {
+                let point = cell + offset;
+                if point.cmplt(IVec2::ZERO).any() || point.cmpge(size).any() {
+                    break;
+                }
+                let rise = (heights[(point.y as usize, point.x as usize)] - here) * vertical_scale;
+                horizon = horizon.max(rise / distance);
+            }
+            // Sine of the horizon's elevation
+            hidden += horizon / (1.0 + horizon * horizon).sqrt();
+        }
+        1.0 - hidden / rays.len() as f32
+    });
+    occlusion
+}
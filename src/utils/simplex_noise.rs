@@ -0,0 +1,187 @@
+use bevy::{math::vec2, prelude::*};
+use std::ops::RangeInclusive;
+
+use super::permutation::PermutationTable;
+use crate::prelude::*;
+
+/// Skew from the square lattice onto the triangular simplex lattice, `(sqrt(3) - 1) / 2`.
+const SKEW: f32 = 0.366_025_42;
+/// Unskew from the triangular simplex lattice back to the square lattice, `(3 - sqrt(3)) / 6`.
+const UNSKEW: f32 = 0.211_324_87;
+
+/// Classic 2D simplex noise, summing radial kernels from the three corners of the triangle containing each point.
+/// Triangles have no preferred direction, so it lacks the grid alignment of Perlin noise.
+pub struct SimplexNoise {
+    pub seed: u64,
+    table: PermutationTable,
+}
+
+impl SimplexNoise {
+    /// Scale bringing the peaks of the kernel sum to 1.
+    const SCALE: f32 = 99.2;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            table: PermutationTable::new(seed),
+        }
+    }
+}
+
+impl NoiseSource for SimplexNoise {
+    /// Returns a value between -1 and 1, with lattice points one unit apart.
+    fn sample(&self, position: Vec2) -> f32 {
+        let skewed = position + (position.x + position.y) * SKEW;
+        let (xi, yi) = (skewed.x.floor() as i32, skewed.y.floor() as i32);
+        let corner = vec2(xi as f32, yi as f32);
+        let offset = position - (corner - (corner.x + corner.y) * UNSKEW);
+
+        // Upper or lower triangle of the skewed cell
+        let middle = if offset.x > offset.y { (1, 0) } else { (0, 1) };
+        let corners = [(0, 0), middle, (1, 1)];
+
+        let value: f32 = corners
+            .iter()
+            .map(|&(dx, dy)| {
+                let delta = offset - vec2(dx as f32, dy as f32) + (dx + dy) as f32 * UNSKEW;
+                kernel(0.5, delta, self.table.gradient(xi + dx, yi + dy))
+            })
+            .sum();
+        value * Self::SCALE
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let skewed = position + (position.x + position.y) * SKEW;
+        let (xi, yi) = (skewed.x.floor() as i32, skewed.y.floor() as i32);
+        let corner = vec2(xi as f32, yi as f32);
+        let offset = position - (corner - (corner.x + corner.y) * UNSKEW);
+
+        let middle = if offset.x > offset.y { (1, 0) } else { (0, 1) };
+        let corners = [(0, 0), middle, (1, 1)];
+
+        let (value, gradient) =
+            corners
+                .iter()
+                .fold((0.0, Vec2::ZERO), |(value, gradient), &(dx, dy)| {
+                    let delta = offset - vec2(dx as f32, dy as f32) + (dx + dy) as f32 * UNSKEW;
+                    let (kernel_value, kernel_gradient) =
+                        kernel_with_gradient(0.5, delta, self.table.gradient(xi + dx, yi + dy));
+                    (value + kernel_value, gradient + kernel_gradient)
+                });
+        (value * Self::SCALE, gradient * Self::SCALE)
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        -1.0..=1.0
+    }
+}
+
+/// OpenSimplex2S 2D noise, the smooth variant of OpenSimplex2.
+/// Sums wider kernels than classic simplex noise from the four lattice points nearest each point,
+/// trading speed for fewer visible triangular artefacts.
+pub struct OpenSimplex2Noise {
+    pub seed: u64,
+    table: PermutationTable,
+}
+
+impl OpenSimplex2Noise {
+    /// Squared radius of each kernel.
+    const RADIUS_SQUARED: f32 = 2.0 / 3.0;
+    /// Reciprocal of the reference implementation's normaliser, bringing the peaks of the kernel sum to 1.
+    const SCALE: f32 = 1.0 / 0.054_818_665;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            table: PermutationTable::new(seed),
+        }
+    }
+
+    /// Skewed lattice cell containing a position, the position's offset from the cell's base point,
+    /// and the four lattice points, relative to the base point, whose kernels reach it.
+    fn nearest_points(position: Vec2) -> ((i32, i32), Vec2, [(i32, i32); 4]) {
+        let skewed = position + (position.x + position.y) * SKEW;
+        let base = skewed.floor();
+        let corner = (base.x as i32, base.y as i32);
+        let offset = position - (base - (base.x + base.y) * UNSKEW);
+
+        // The base point and its opposite corner always reach the point. Which two others do
+        // depends on which side of the cell's diagonal it is on, and how near the cell's other corners.
+        let inside = skewed - base;
+        let (x, y) = (inside.x, inside.y);
+        let others = if x + y > 1.0 {
+            [
+                if 2.0 * x - y > 1.0 { (2, 1) } else { (0, 1) },
+                if 2.0 * y - x > 1.0 { (1, 2) } else { (1, 0) },
+            ]
+        } else {
+            [
+                if 2.0 * x - y < 0.0 { (-1, 0) } else { (1, 0) },
+                if 2.0 * y - x < 0.0 { (0, -1) } else { (0, 1) },
+            ]
+        };
+        (corner, offset, [(0, 0), (1, 1), others[0], others[1]])
+    }
+}
+
+impl NoiseSource for OpenSimplex2Noise {
+    /// Returns a value between -1 and 1, with lattice points one unit apart.
+    fn sample(&self, position: Vec2) -> f32 {
+        let ((xi, yi), offset, points) = Self::nearest_points(position);
+        let value: f32 = points
+            .iter()
+            .map(|&(dx, dy)| {
+                let delta = offset - vec2(dx as f32, dy as f32) + (dx + dy) as f32 * UNSKEW;
+                kernel(
+                    Self::RADIUS_SQUARED,
+                    delta,
+                    self.table.gradient(xi + dx, yi + dy),
+                )
+            })
+            .sum();
+        value * Self::SCALE
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let ((xi, yi), offset, points) = Self::nearest_points(position);
+        let (value, gradient) =
+            points
+                .iter()
+                .fold((0.0, Vec2::ZERO), |(value, gradient), &(dx, dy)| {
+                    let delta = offset - vec2(dx as f32, dy as f32) + (dx + dy) as f32 * UNSKEW;
+                    let (kernel_value, kernel_gradient) = kernel_with_gradient(
+                        Self::RADIUS_SQUARED,
+                        delta,
+                        self.table.gradient(xi + dx, yi + dy),
+                    );
+                    (value + kernel_value, gradient + kernel_gradient)
+                });
+        (value * Self::SCALE, gradient * Self::SCALE)
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        -1.0..=1.0
+    }
+}
+
+/// Contribution of one lattice point, fading to zero at the kernel radius.
+fn kernel(radius_squared: f32, delta: Vec2, gradient: Vec2) -> f32 {
+    let falloff = radius_squared - delta.length_squared();
+    if falloff <= 0.0 {
+        return 0.0;
+    }
+    falloff.powi(4) * gradient.dot(delta)
+}
+
+/// Contribution of one lattice point and its gradient with respect to `delta`.
+fn kernel_with_gradient(radius_squared: f32, delta: Vec2, gradient: Vec2) -> (f32, Vec2) {
+    let falloff = radius_squared - delta.length_squared();
+    if falloff <= 0.0 {
+        return (0.0, Vec2::ZERO);
+    }
+    let ramp = gradient.dot(delta);
+    (
+        falloff.powi(4) * ramp,
+        falloff.powi(4) * gradient - 8.0 * falloff.powi(3) * ramp * delta,
+    )
+}
@@ -0,0 +1,82 @@
+use bevy::{math::vec2, prelude::*};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::ops::RangeInclusive;
+
+use super::permutation::PermutationTable;
+use crate::prelude::*;
+
+/// Value noise, smoothly interpolating random heights at the lattice points.
+/// Cheaper than gradient noise, but blockier, with peaks and troughs sitting on the lattice.
+pub struct ValueNoise {
+    pub seed: u64,
+    table: PermutationTable,
+    values: [f32; 256],
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut values = [0.0; 256];
+        for value in &mut values {
+            *value = rng.gen_range(-1.0..=1.0);
+        }
+        Self {
+            seed,
+            table: PermutationTable::new(rng.gen()),
+            values,
+        }
+    }
+
+    fn value(&self, x: i32, y: i32) -> f32 {
+        self.values[self.table.hash(x, y)]
+    }
+}
+
+impl NoiseSource for ValueNoise {
+    /// Returns a value between -1 and 1, with lattice points one unit apart.
+    fn sample(&self, position: Vec2) -> f32 {
+        let (xi, yi) = (position.x.floor() as i32, position.y.floor() as i32);
+        let tx = smootherstep(position.x - xi as f32);
+        let ty = smootherstep(position.y - yi as f32);
+
+        let top = lerp(self.value(xi, yi), self.value(xi + 1, yi), tx);
+        let bottom = lerp(self.value(xi, yi + 1), self.value(xi + 1, yi + 1), tx);
+        lerp(top, bottom, ty)
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let (xi, yi) = (position.x.floor() as i32, position.y.floor() as i32);
+        let (xf, yf) = (position.x - xi as f32, position.y - yi as f32);
+        let (tx, ty) = (smootherstep(xf), smootherstep(yf));
+
+        let a = self.value(xi, yi);
+        let b = self.value(xi + 1, yi);
+        let c = self.value(xi, yi + 1);
+        let d = self.value(xi + 1, yi + 1);
+        let k = a - b - c + d;
+        let value = a + tx * (b - a) + ty * (c - a) + tx * ty * k;
+        let gradient = vec2(
+            smootherstep_derivative(xf) * (b - a + ty * k),
+            smootherstep_derivative(yf) * (c - a + tx * k),
+        );
+        (value, gradient)
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        -1.0..=1.0
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+/// Quintic easing, with zero first and second derivatives at both ends to hide the lattice.
+fn smootherstep(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn smootherstep_derivative(t: f32) -> f32 {
+    30.0 * t * t * (t - 1.0) * (t - 1.0)
+}
@@ -0,0 +1,186 @@
+use bevy::{math::vec2, prelude::*};
+use ndarray::Array2;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+
+use crate::prelude::*;
+
+/// How distances to feature points are measured.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// Straight-line distance, giving rounded cells.
+    #[default]
+    Euclidean,
+    /// Sum of the distances along each axis, giving diamond-shaped cells.
+    Manhattan,
+    /// Largest distance along either axis, giving square cells.
+    Chebyshev,
+}
+
+impl DistanceMetric {
+    pub fn distance(&self, delta: Vec2) -> f32 {
+        match self {
+            Self::Euclidean => delta.length(),
+            Self::Manhattan => delta.x.abs() + delta.y.abs(),
+            Self::Chebyshev => delta.x.abs().max(delta.y.abs()),
+        }
+    }
+
+    /// Gradient of the distance with respect to `delta`.
+    pub fn gradient(&self, delta: Vec2) -> Vec2 {
+        match self {
+            Self::Euclidean => delta.normalize_or_zero(),
+            Self::Manhattan => vec2(sign(delta.x), sign(delta.y)),
+            Self::Chebyshev if delta.x.abs() >= delta.y.abs() => vec2(sign(delta.x), 0.0),
+            Self::Chebyshev => vec2(0.0, sign(delta.y)),
+        }
+    }
+}
+
+/// Which value `WorleyNoise` returns when used as a `NoiseSource`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WorleyFeature {
+    /// Distance to the nearest feature point, low at cell centres.
+    #[default]
+    F1,
+    /// Distance to the second nearest feature point.
+    F2,
+    /// `F2 - F1`, zero along the borders between cells, for cracks and ridges.
+    Difference,
+}
+
+/// Distances from a position to its nearest feature points, in grid cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorleySample {
+    pub f1: f32,
+    pub f2: f32,
+    /// Index of the grid cell owning the nearest feature point, `y * width + x`.
+    pub cell_id: u32,
+    /// Offset of the position from the nearest feature point, in grid cells.
+    pub f1_delta: Vec2,
+    /// Offset of the position from the second nearest feature point, in grid cells.
+    pub f2_delta: Vec2,
+}
+
+impl WorleySample {
+    pub fn difference(&self) -> f32 {
+        self.f2 - self.f1
+    }
+}
+
+/// Worley, or cellular, noise: distances to one random feature point in each cell of a grid.
+/// Like `PerlinNoise`, the grid tiles across the unit square, so the noise wraps at the map edges.
+pub struct WorleyNoise {
+    pub seed: u64,
+    pub metric: DistanceMetric,
+    pub feature: WorleyFeature,
+    /// Feature point within each cell, as an offset from the cell's top left corner.
+    pub points: Array2<Vec2>,
+}
+
+impl WorleyNoise {
+    pub fn new(seed: u64, (width, height): (usize, usize), metric: DistanceMetric) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let points = Array2::from_shape_simple_fn((height.max(1), width.max(1)), || {
+            vec2(rng.gen(), rng.gen())
+        });
+        Self {
+            seed,
+            metric,
+            feature: WorleyFeature::F1,
+            points,
+        }
+    }
+
+    pub fn with_feature(mut self, feature: WorleyFeature) -> Self {
+        self.feature = feature;
+        self
+    }
+
+    /// Nearest feature points to a position given as a fraction of the map.
+    pub fn cells(&self, position: Vec2) -> WorleySample {
+        let (height, width) = self.points.dim();
+        let position = position * vec2(width as f32, height as f32);
+        let (xi, yi) = (position.x.floor() as i32, position.y.floor() as i32);
+
+        // Points two cells away can still be the second nearest
+        let mut nearest = WorleySample {
+            f1: f32::MAX,
+            f2: f32::MAX,
+            cell_id: 0,
+            f1_delta: Vec2::ZERO,
+            f2_delta: Vec2::ZERO,
+        };
+        for dy in -2..=2 {
+            for dx in -2..=2 {
+                let (x, y) = (xi + dx, yi + dy);
+                let wrapped = (
+                    y.rem_euclid(height as i32) as usize,
+                    x.rem_euclid(width as i32) as usize,
+                );
+                let point = vec2(x as f32, y as f32) + self.points[wrapped];
+                let delta = position - point;
+                let distance = self.metric.distance(delta);
+                if distance < nearest.f1 {
+                    nearest.f2 = nearest.f1;
+                    nearest.f2_delta = nearest.f1_delta;
+                    nearest.f1 = distance;
+                    nearest.f1_delta = delta;
+                    nearest.cell_id = (wrapped.0 * width + wrapped.1) as u32;
+                } else if distance < nearest.f2 {
+                    nearest.f2 = distance;
+                    nearest.f2_delta = delta;
+                }
+            }
+        }
+        nearest
+    }
+}
+
+impl NoiseSource for WorleyNoise {
+    /// The selected feature, with positions as fractions of the map.
+    fn sample(&self, position: Vec2) -> f32 {
+        let sample = self.cells(position);
+        match self.feature {
+            WorleyFeature::F1 => sample.f1,
+            WorleyFeature::F2 => sample.f2,
+            WorleyFeature::Difference => sample.difference(),
+        }
+    }
+
+    /// Exact except on cell borders, where the nearest feature point changes.
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        let sample = self.cells(position);
+        let f1_gradient = self.metric.gradient(sample.f1_delta);
+        let f2_gradient = self.metric.gradient(sample.f2_delta);
+        let (value, gradient) = match self.feature {
+            WorleyFeature::F1 => (sample.f1, f1_gradient),
+            WorleyFeature::F2 => (sample.f2, f2_gradient),
+            WorleyFeature::Difference => (sample.difference(), f2_gradient - f1_gradient),
+        };
+        let (height, width) = self.points.dim();
+        (value, gradient * vec2(width as f32, height as f32))
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        // The nearest point is at worst in the far corner of the same cell,
+        // and the second nearest in the far corner of the next cell along
+        let max_f1 = self.metric.distance(vec2(1.0, 1.0));
+        let max_f2 = self.metric.distance(vec2(2.0, 1.0));
+        match self.feature {
+            WorleyFeature::F1 => 0.0..=max_f1,
+            WorleyFeature::F2 | WorleyFeature::Difference => 0.0..=max_f2,
+        }
+    }
+}
+
+/// Sign of a value, or zero for zero, unlike `f32::signum`.
+fn sign(value: f32) -> f32 {
+    if value == 0.0 {
+        0.0
+    } else {
+        value.signum()
+    }
+}
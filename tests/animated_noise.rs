@@ -0,0 +1,24 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+
+fn sample_grid(noise: &AnimatedNoise) -> Vec<f32> {
+    (0..32 * 32)
+        .map(|index| noise.sample(vec2((index % 32) as f32, (index / 32) as f32) / 32.0))
+        .collect()
+}
+
+fn largest_change(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .fold(0.0, |largest, (a, b)| largest.max((a - b).abs()))
+}
+
+/// Animated noise should change smoothly with time, and be the same whenever it returns to a time.
+#[test]
+fn animated_noise_changes_smoothly() {
+    let at_time = |time| sample_grid(&AnimatedNoise::new(9, Vec2::splat(4.0)).at_time(time));
+    let start = at_time(0.3);
+    assert_eq!(start, at_time(0.3));
+    assert!(largest_change(&start, &at_time(0.31)) < 0.05);
+    assert!(largest_change(&start, &at_time(1.8)) > 0.3);
+}
@@ -0,0 +1,33 @@
+use islands::prelude::*;
+use ndarray::Array2;
+
+/// Smoothly joined islands should blend where they meet, but leave the open sea flat.
+#[test]
+fn smooth_union_keeps_open_sea() {
+    let union = IslandUnion::Smooth { k: 0.2 };
+    assert_eq!(union.combine(0.0, 0.0), 0.0);
+    assert_eq!(union.combine(0.9, 0.2), 0.9);
+    assert!(union.combine(0.5, 0.5) > 0.5);
+
+    // Far from every island, whatever the number of islands
+    let sea = (0..6).fold(0.0, |mask, _| union.combine(mask, 1.0e-6));
+    assert!(sea < 1.0e-5, "open sea mask is {}", sea);
+}
+
+/// Zero or negative radii should give islands a cell across rather than dividing by zero.
+#[test]
+fn degenerate_radii_are_raised_to_one_cell() {
+    for radius in [[0.0, 0.0], [-0.1, 0.0]] {
+        let stage = ArchipelagoStage {
+            radius,
+            ..Default::default()
+        };
+        let sites = stage.sites(100, 80, 3);
+        assert!(!sites.is_empty());
+        assert!(sites.iter().all(|site| site.radius == 1.0));
+
+        let mut height_map = Array2::from_elem((80, 100), 1.0);
+        stage.apply(&mut height_map, &mut TerrainLayers::default(), 3);
+        assert!(height_map.iter().all(|height| height.is_finite()));
+    }
+}
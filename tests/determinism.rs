@@ -0,0 +1,249 @@
+use bevy::math::vec2;
+use islands::prelude::*;
+use ndarray::Array2;
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 96;
+
+/// Stages with random or parallel parts, in a single pipeline.
+fn pipeline() -> TerrainPipeline {
+    TerrainPipeline::new()
+        .with_stage(FractalNoiseStage {
+            warp: Some(WarpSettings::default()),
+            ..Default::default()
+        })
+        .with_stage(NormaliseStage)
+        .with_stage(ArchipelagoStage::default())
+        .with_stage(HydraulicErosionStage {
+            iterations: 2_000,
+            ..Default::default()
+        })
+        .with_stage(ThermalErosionStage::default())
+        .with_stage(RiverStage {
+            threshold: 100.0,
+            ..Default::default()
+        })
+        .with_stage(LakeStage::default())
+}
+
+fn run_on_threads<T: Send>(threads: usize, function: impl FnOnce() -> T + Send) -> T {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(function)
+}
+
+fn assert_bit_identical(a: &Array2<f32>, b: &Array2<f32>, what: &str) {
+    assert_eq!(a.dim(), b.dim(), "{} sizes differ", what);
+    let differing = a
+        .iter()
+        .zip(b.iter())
+        .filter(|(a, b)| a.to_bits() != b.to_bits())
+        .count();
+    assert_eq!(differing, 0, "{} differs in {} cells", what, differing);
+}
+
+fn assert_same_generation(a: &(Array2<f32>, TerrainLayers), b: &(Array2<f32>, TerrainLayers)) {
+    assert_bit_identical(&a.0, &b.0, "height map");
+    assert_bit_identical(
+        a.1.erosion.as_ref().unwrap(),
+        b.1.erosion.as_ref().unwrap(),
+        "erosion map",
+    );
+    assert_bit_identical(
+        a.1.rivers.as_ref().unwrap(),
+        b.1.rivers.as_ref().unwrap(),
+        "rivers",
+    );
+    assert_eq!(a.1.lake_ids, b.1.lake_ids, "lakes differ");
+}
+
+/// The same seed should always generate exactly the same terrain, and a different seed different terrain.
+#[test]
+fn same_seed_generates_same_terrain() {
+    let pipeline = pipeline();
+    let first = pipeline.generate(WIDTH, HEIGHT, 7);
+    let second = pipeline.generate(WIDTH, HEIGHT, 7);
+    assert_same_generation(&first, &second);
+
+    let other = pipeline.generate(WIDTH, HEIGHT, 8);
+    assert_ne!(
+        first.0, other.0,
+        "different seeds generated the same terrain"
+    );
+}
+
+/// Splitting the work across any number of threads should not change the result.
+#[test]
+fn thread_count_does_not_change_terrain() {
+    let pipeline = pipeline();
+    let single = run_on_threads(1, || pipeline.generate(WIDTH, HEIGHT, 7));
+    for threads in [2, 3, 8] {
+        let parallel = run_on_threads(threads, || pipeline.generate(WIDTH, HEIGHT, 7));
+        assert_same_generation(&single, &parallel);
+    }
+}
+
+/// Parallel maps and thermal erosion should not depend on the order rows are visited or combined in.
+#[test]
+fn parallel_stages_do_not_depend_on_order() {
+    let (rough, _) = TerrainPipeline::new()
+        .with_stage(NoiseStage::new(vec![
+            (vec2(17.0, 13.0), 1.0),
+            (vec2(61.0, 47.0), 0.5),
+        ]))
+        .with_stage(NormaliseStage)
+        .generate(WIDTH, HEIGHT, 3);
+
+    let map = |threads| {
+        run_on_threads(threads, || {
+            let mut map = rough.clone();
+            par_map_indexed(&mut map, |(yi, xi), value| {
+                value * (yi as f32 * 0.37 + xi as f32 * 0.11).sin()
+            });
+            map
+        })
+    };
+    let erode = |threads| {
+        run_on_threads(threads, || {
+            let mut height_map = rough.clone();
+            let mut layers = TerrainLayers::default();
+            ThermalErosionStage {
+                iterations: 50,
+                ..Default::default()
+            }
+            .apply(&mut height_map, &mut layers, 3);
+            (height_map, layers.erosion.unwrap())
+        })
+    };
+
+    let single_map = map(1);
+    let (single_height_map, single_erosion) = erode(1);
+    for threads in [2, 5, 16] {
+        assert_bit_identical(&single_map, &map(threads), "parallel map");
+        let (height_map, erosion) = erode(threads);
+        assert_bit_identical(
+            &single_height_map,
+            &height_map,
+            "thermally eroded height map",
+        );
+        assert_bit_identical(&single_erosion, &erosion, "thermal erosion map");
+    }
+}
+
+/// FNV-1a hash of the exact bits of a map, which is stable across platforms and Rust versions.
+fn hash_bits(map: &Array2<f32>) -> u64 {
+    map.iter()
+        .flat_map(|value| value.to_bits().to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Hashes of terrain generated on the machine these were checked in from, which every machine should match.
+/// Regenerate them when a change to generation is intended, from the listing this test prints on failure.
+#[test]
+fn terrain_matches_golden_hashes() {
+    const GOLDEN: &[(&str, u64)] = &[
+        ("Perlin", 0x9fcf61aeb838b5c6),
+        ("Simplex", 0xad64eccc1c830144),
+        ("OpenSimplex2", 0x31dbe9550ef34544),
+        ("Value", 0x83878c894ff40403),
+        ("PerlinTorus", 0x9027abe45ebdbce2),
+        ("Worley", 0x9fc9aa0d6232dc1d),
+        ("Gaussian", 0x95adbef71f4ccc09),
+        ("Squircle", 0x64c8489b22b85e9b),
+        ("Ellipse", 0xf9536546d2d06a11),
+        ("NoisyRadius", 0xa0b7ad48699cb188),
+        ("Ring", 0x0d4e88c6e77e71b9),
+        ("Crescent", 0x9c0581b1a7df9d7a),
+        ("Pipeline", 0x91e546dc09184749),
+        ("PipelineRivers", 0x68c00ea49d512325),
+    ];
+
+    let layers = vec![(vec2(3.0, 3.0), 1.0), (vec2(8.0, 8.0), 0.4)];
+    let algorithms = [
+        ("Perlin", NoiseAlgorithm::Perlin),
+        ("Simplex", NoiseAlgorithm::Simplex),
+        ("OpenSimplex2", NoiseAlgorithm::OpenSimplex2),
+        ("Value", NoiseAlgorithm::Value),
+        ("PerlinTorus", NoiseAlgorithm::PerlinTorus),
+        (
+            "Worley",
+            NoiseAlgorithm::Worley {
+                metric: DistanceMetric::Euclidean,
+                feature: WorleyFeature::F1,
+            },
+        ),
+    ];
+    let shapes = [
+        ("Gaussian", FalloffShape::Gaussian),
+        ("Squircle", FalloffShape::Squircle { exponent: 3.5 }),
+        (
+            "Ellipse",
+            FalloffShape::Ellipse {
+                aspect: 1.8,
+                rotation: 30.0,
+            },
+        ),
+        (
+            "NoisyRadius",
+            FalloffShape::NoisyRadius {
+                amplitude: 0.3,
+                harmonics: 6,
+            },
+        ),
+        (
+            "Ring",
+            FalloffShape::Ring {
+                radius: 1.2,
+                thickness: 0.3,
+            },
+        ),
+        (
+            "Crescent",
+            FalloffShape::Crescent {
+                shift: 0.5,
+                inner_radius: 0.7,
+                rotation: 120.0,
+            },
+        ),
+    ];
+
+    let mut hashes = Vec::new();
+    for (name, algorithm) in algorithms {
+        let (height_map, _) = TerrainPipeline::new()
+            .with_stage(NoiseStage::new(layers.clone()).with_algorithm(algorithm))
+            .generate(64, 48, 11);
+        hashes.push((name.to_string(), hash_bits(&height_map)));
+    }
+    for (name, shape) in shapes {
+        let (height_map, _) = TerrainPipeline::new()
+            .with_stage(NoiseStage::new(layers.clone()))
+            .with_stage(NormaliseStage)
+            .with_stage(FalloffStage::new(vec2(0.45, 0.55), 0.25).with_shape(shape))
+            .generate(64, 48, 11);
+        hashes.push((name.to_string(), hash_bits(&height_map)));
+    }
+    let (height_map, layers) = pipeline().generate(WIDTH, HEIGHT, 7);
+    hashes.push(("Pipeline".to_string(), hash_bits(&height_map)));
+    hashes.push((
+        "PipelineRivers".to_string(),
+        hash_bits(layers.rivers.as_ref().unwrap()),
+    ));
+
+    let listing: String = hashes
+        .iter()
+        .map(|(name, hash)| format!("        (\"{}\", {:#018x}),\n", name, hash))
+        .collect();
+    let expected: Vec<(String, u64)> = GOLDEN
+        .iter()
+        .map(|(name, hash)| (name.to_string(), *hash))
+        .collect();
+    assert!(
+        hashes == expected,
+        "generated terrain differs from the golden hashes, which are now:\n{}",
+        listing
+    );
+}
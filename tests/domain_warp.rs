@@ -0,0 +1,28 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+/// Without any strength the warp should leave positions alone, so the warped noise is exactly its source.
+#[test]
+fn zero_strength_matches_source() {
+    let source = || PerlinNoise::infinite(16, vec![(vec2(3.0, 3.0), 1.0), (vec2(7.0, 7.0), 0.5)]);
+    let plain = source();
+    let mut rng = ChaCha8Rng::seed_from_u64(17);
+    let positions: Vec<Vec2> = (0..500)
+        .map(|_| vec2(rng.gen_range(-2.0..2.0), rng.gen_range(-2.0..2.0)))
+        .collect();
+    for iterations in [1, 3] {
+        let warp = DomainWarp::new(Box::new(source()), Box::new(SimplexNoise::new(18)), 0.0)
+            .with_iterations(iterations);
+        assert_eq!(warp.range(), plain.range());
+        for &position in &positions {
+            assert_eq!(warp.warped(position), position);
+            assert_eq!(warp.sample(position), plain.sample(position));
+            assert_eq!(
+                warp.sample_with_gradient(position),
+                plain.sample_with_gradient(position)
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use islands::prelude::*;
+use ndarray::Array2;
+
+const WIDTH: usize = 21;
+const HEIGHT: usize = 16;
+const OUTLET: (usize, usize) = (0, WIDTH / 2);
+
+/// V-shaped valley running up from a single gap in a high wall around the map, with a pit part way up its floor.
+fn valley() -> Array2<f32> {
+    let mut height_map = Array2::from_shape_fn((HEIGHT, WIDTH), |(yi, xi)| {
+        let across = (xi as f32 - OUTLET.1 as f32).abs();
+        0.1 + 0.02 * across + 0.01 * yi as f32
+    });
+    for ((yi, xi), height) in height_map.indexed_iter_mut() {
+        if yi == 0 || xi == 0 || yi == HEIGHT - 1 || xi == WIDTH - 1 {
+            *height = 1.0;
+        }
+    }
+    height_map[OUTLET] = 0.0;
+    height_map[(HEIGHT / 2, OUTLET.1)] = 0.05;
+    height_map
+}
+
+fn is_border((yi, xi): (usize, usize)) -> bool {
+    yi == 0 || xi == 0 || yi == HEIGHT - 1 || xi == WIDTH - 1
+}
+
+/// Every cell inside the wall should drain through the valley to the outlet, filling the pit on the way, without cycles.
+#[test]
+fn valley_drains_to_outlet() {
+    let height_map = valley();
+    let drainage = Drainage::new(&height_map);
+
+    // Filling only ever raises cells, and the pit fills to where it spills down the valley
+    assert!(drainage
+        .filled
+        .iter()
+        .zip(height_map.iter())
+        .all(|(filled, ground)| filled >= ground));
+    assert!(drainage.filled[(HEIGHT / 2, OUTLET.1)] > height_map[(HEIGHT / 2, OUTLET.1)]);
+
+    let interior: Vec<(usize, usize)> = height_map
+        .indexed_iter()
+        .map(|(index, _)| index)
+        .filter(|&index| !is_border(index))
+        .collect();
+    for &start in &interior {
+        let mut cell = start;
+        let mut steps = 0;
+        while let Some(receiver) = drainage.receivers[cell] {
+            cell = receiver;
+            steps += 1;
+            assert!(steps <= height_map.len(), "{:?} drains in a cycle", start);
+        }
+        assert_eq!(cell, OUTLET, "{:?} drains to {:?}", start, cell);
+    }
+
+    // Every cell is ordered after the cell it drains into
+    let mut position = Array2::zeros(height_map.dim());
+    for (order, &index) in drainage.order.iter().enumerate() {
+        position[index] = order;
+    }
+    assert_eq!(drainage.order.len(), height_map.len());
+    for (index, receiver) in drainage.receivers.indexed_iter() {
+        if let Some(receiver) = *receiver {
+            assert!(position[receiver] < position[index]);
+        }
+    }
+
+    let accumulation = drainage.flow_accumulation();
+    assert_eq!(accumulation[OUTLET], (interior.len() + 1) as f32);
+}
+
+/// The valley floor, where all of the water gathers, should become a river.
+#[test]
+fn valley_floor_becomes_river() {
+    let mut height_map = valley();
+    let mut layers = TerrainLayers::default();
+    RiverStage {
+        threshold: 20.0,
+        carve_depth: 0.001,
+        sea_level: 0.0,
+    }
+    .apply(&mut height_map, &mut layers, 0);
+
+    let rivers = layers.rivers.unwrap();
+    assert!(rivers[(1, OUTLET.1)] > 0.0 && rivers[(3, OUTLET.1)] > 0.0);
+    assert_eq!(rivers[(1, 2)], 0.0);
+    assert_eq!(rivers[(HEIGHT - 2, WIDTH - 2)], 0.0);
+}
@@ -0,0 +1,102 @@
+use bevy::math::vec2;
+use islands::prelude::*;
+use ndarray::Array2;
+
+/// Rough hills with plenty of slopes steeper than the talus angle.
+fn hills(width: usize, height: usize) -> Array2<f32> {
+    TerrainPipeline::new()
+        .with_stage(NoiseStage::new(vec![
+            (vec2(4.0, 4.0), 1.0),
+            (vec2(16.0, 16.0), 0.5),
+        ]))
+        .with_stage(NormaliseStage)
+        .generate(width, height, 21)
+        .0
+}
+
+/// Total of the heights, added up in double precision.
+fn volume(height_map: &Array2<f32>) -> f64 {
+    height_map.iter().map(|&height| height as f64).sum()
+}
+
+/// Steepest drop from any cell to a neighbour, per cell of distance.
+fn steepest_slope(height_map: &Array2<f32>) -> f32 {
+    let (height, width) = height_map.dim();
+    let mut steepest: f32 = 0.0;
+    for ((yi, xi), &ground) in height_map.indexed_iter() {
+        for neighbour in neighbours((yi, xi), (height, width)) {
+            let distance = if neighbour.0 != yi && neighbour.1 != xi {
+                std::f32::consts::SQRT_2
+            } else {
+                1.0
+            };
+            steepest = steepest.max((ground - height_map[neighbour]) / distance);
+        }
+    }
+    steepest
+}
+
+/// Thermal erosion should only move material around, and given long enough, leave no slope steeper than the talus angle.
+#[test]
+fn thermal_erosion_settles_to_talus_angle() {
+    let stage = ThermalErosionStage {
+        iterations: 1_500,
+        talus_angle: 40.0,
+        cell_size: 0.01,
+        rate: 0.5,
+    };
+    let talus = stage.talus_angle.to_radians().tan() * stage.cell_size;
+    let mut height_map = hills(32, 24);
+    assert!(
+        steepest_slope(&height_map) > 2.0 * talus,
+        "the hills start out too gentle"
+    );
+    let before = volume(&height_map);
+
+    let mut layers = TerrainLayers::default();
+    stage.apply(&mut height_map, &mut layers, 0);
+
+    assert!(
+        steepest_slope(&height_map) <= talus * 1.01,
+        "slope of {} left with a talus slope of {}",
+        steepest_slope(&height_map),
+        talus
+    );
+    let after = volume(&height_map);
+    assert!(
+        (after - before).abs() < 1.0e-6 * before,
+        "volume changed from {} to {}",
+        before,
+        after
+    );
+    let moved = volume(layers.erosion.as_ref().unwrap());
+    assert!(moved.abs() < 1.0e-3, "erosion layer totals {}", moved);
+}
+
+/// Hydraulic erosion should never add material, only move it or wash it off the map,
+/// and the same seed should always erode the same way.
+#[test]
+fn hydraulic_erosion_removes_material_deterministically() {
+    let stage = HydraulicErosionStage {
+        iterations: 5_000,
+        ..Default::default()
+    };
+    let erode = |seed| {
+        let mut height_map = hills(64, 48);
+        let mut layers = TerrainLayers::default();
+        stage.apply(&mut height_map, &mut layers, seed);
+        height_map
+    };
+
+    let original = hills(64, 48);
+    let eroded = erode(4);
+    assert_ne!(eroded, original, "nothing was eroded");
+    assert!(
+        volume(&eroded) <= volume(&original),
+        "volume grew from {} to {}",
+        volume(&original),
+        volume(&eroded)
+    );
+    assert_eq!(eroded, erode(4), "the same seed eroded differently");
+    assert_ne!(eroded, erode(5), "different seeds eroded the same way");
+}
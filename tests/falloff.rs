@@ -0,0 +1,92 @@
+use bevy::{
+    math::{vec2, Vec2},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use islands::prelude::*;
+use ndarray::{array, Array2};
+
+/// Directions out from the centre, all the way round.
+fn directions() -> impl Iterator<Item = Vec2> {
+    (0..16).map(|i| Vec2::from_angle(i as f32 / 16.0 * std::f32::consts::TAU))
+}
+
+/// An atoll should be highest along its ring and fall away on both sides.
+#[test]
+fn ring_peaks_at_its_radius() {
+    let shape = FalloffShape::Ring {
+        radius: 0.8,
+        thickness: 0.2,
+    };
+    for direction in directions() {
+        let peak = (0..=200)
+            .map(|i| i as f32 * 0.01)
+            .max_by(|a, b| {
+                shape
+                    .mask(direction * *a, 0)
+                    .total_cmp(&shape.mask(direction * *b, 0))
+            })
+            .unwrap();
+        assert!(
+            (peak - 0.8).abs() < 1.0e-4,
+            "peaks at {} towards {}",
+            peak,
+            direction
+        );
+        assert!((shape.mask(direction * 0.8, 0) - 1.0).abs() < 1.0e-6);
+        assert!(shape.mask(Vec2::ZERO, 0) < 1.0e-3);
+    }
+}
+
+/// Nothing should be left inside the bite taken out of a crescent, and the far side of the disc should be untouched.
+#[test]
+fn crescent_is_zero_inside_its_inner_circle() {
+    let shape = FalloffShape::Crescent {
+        shift: 0.5,
+        inner_radius: 0.7,
+        rotation: 90.0,
+    };
+    let bite_centre = vec2(0.0, 0.5);
+    for direction in directions() {
+        for distance in [0.0, 0.2, 0.4, 0.6, 0.69] {
+            let offset = bite_centre + direction * distance;
+            assert_eq!(shape.mask(offset, 0), 0.0, "land at {}", offset);
+        }
+    }
+    assert!(shape.mask(vec2(0.0, -0.5), 0) > 0.3);
+    assert!(shape.mask(vec2(0.0, -0.5), 0) <= FalloffShape::Quadratic.mask(vec2(0.0, -0.5), 0));
+}
+
+/// A mask image should be read from its first channel and stretched over the map, with cell centres lined up.
+#[test]
+fn mask_image_scales_heights() {
+    let image = |data: Vec<u8>, format| {
+        Image::new(
+            Extent3d {
+                width: 2,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+            RenderAssetUsages::default(),
+        )
+    };
+    let expected = array![[0.0, 0.25, 0.75, 1.0], [0.0, 0.25, 0.75, 1.0]];
+    for image in [
+        image(vec![0, 255], TextureFormat::R8Unorm),
+        image(vec![0, 9, 9, 9, 255, 9, 9, 9], TextureFormat::Rgba8Unorm),
+        image(vec![0, 0, 255, 255], TextureFormat::R16Unorm),
+    ] {
+        let stage = MaskStage::from_image(&image).unwrap();
+        assert_eq!(stage.mask, array![[0.0, 1.0]]);
+        let mut height_map = Array2::from_elem((2, 4), 1.0);
+        stage.apply(&mut height_map, &mut TerrainLayers::default(), 0);
+        assert_eq!(height_map, expected);
+    }
+    assert!(MaskStage::from_image(&image(vec![0; 8], TextureFormat::R32Float)).is_none());
+}
@@ -0,0 +1,126 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::ops::RangeInclusive;
+
+const KINDS: [FractalKind; 5] = [
+    FractalKind::Fbm,
+    FractalKind::Ridged,
+    FractalKind::Billow,
+    FractalKind::Turbulence,
+    FractalKind::Eroded,
+];
+
+/// The same value everywhere, so every octave is the same and the sums can be worked out by hand.
+struct Constant(f32);
+
+impl NoiseSource for Constant {
+    fn sample(&self, _position: Vec2) -> f32 {
+        self.0
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        self.0..=self.0
+    }
+}
+
+/// Rising steadily along x, with the same slope everywhere.
+struct Ramp(f32);
+
+impl NoiseSource for Ramp {
+    fn sample(&self, position: Vec2) -> f32 {
+        self.0 * position.x
+    }
+
+    fn sample_with_gradient(&self, position: Vec2) -> (f32, Vec2) {
+        (self.sample(position), vec2(self.0, 0.0))
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        f32::MIN..=f32::MAX
+    }
+}
+
+fn fractal(source: impl NoiseSource + 'static, kind: FractalKind, gain: f32) -> Fractal {
+    Fractal::new(
+        Box::new(source),
+        FractalSettings {
+            kind,
+            octaves: 3,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            gain,
+            offset: 1.0,
+        },
+    )
+}
+
+/// Octaves of amplitude 1, 0.5 and 0.25 should be combined as each kind describes.
+#[test]
+fn kinds_combine_octaves_as_described() {
+    let position = vec2(0.3, 0.7);
+    let expected = [
+        (FractalKind::Fbm, -0.6 * 1.75),
+        (FractalKind::Billow, (2.0 * 0.6 - 1.0) * 1.75),
+        (FractalKind::Turbulence, 0.6 * 1.75),
+        // Crests of (1 - 0.6)^2 = 0.16, each weighted by twice the one before
+        (
+            FractalKind::Ridged,
+            0.16 + 0.5 * 0.16 * 0.32 + 0.25 * 0.16 * 0.1024,
+        ),
+        // Nothing to damp on a flat source
+        (FractalKind::Eroded, -0.6 * 1.75),
+    ];
+    for (kind, expected) in expected {
+        let value = fractal(Constant(-0.6), kind, 2.0).sample(position);
+        assert!(
+            (value - expected).abs() < 1.0e-6,
+            "{:?} is {}, not {}",
+            kind,
+            value,
+            expected
+        );
+    }
+}
+
+/// Eroded octaves should shrink where the octaves before them are steep, and match fBm when the gain is zero.
+#[test]
+fn eroded_damps_detail_on_slopes() {
+    for x in [0.1, 0.5, 2.0] {
+        let position = vec2(x, 0.4);
+        let fbm = fractal(Ramp(3.0), FractalKind::Fbm, 2.0).sample(position);
+        let undamped = fractal(Ramp(3.0), FractalKind::Eroded, 0.0).sample(position);
+        let eroded = fractal(Ramp(3.0), FractalKind::Eroded, 2.0).sample(position);
+        assert_eq!(undamped, fbm);
+        assert!(
+            eroded > 0.0 && eroded < 0.2 * fbm,
+            "{} against {}",
+            eroded,
+            fbm
+        );
+    }
+}
+
+/// Every kind should stay within the range it reports, so normalising by it is safe.
+#[test]
+fn kinds_stay_within_their_range() {
+    let mut rng = ChaCha8Rng::seed_from_u64(14);
+    let positions: Vec<Vec2> = (0..1000)
+        .map(|_| vec2(rng.gen_range(0.0..4.0), rng.gen_range(0.0..4.0)))
+        .collect();
+    for kind in KINDS {
+        let noise = fractal(SimplexNoise::new(15), kind, 2.0);
+        let range = noise.range();
+        for &position in &positions {
+            let value = noise.sample(position);
+            assert!(
+                range.contains(&value),
+                "{:?} gives {} outside {:?}",
+                kind,
+                value,
+                range
+            );
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::math::vec2;
+use islands::prelude::*;
+use ndarray::Array2;
+
+/// Noise, normalise and falloff stages should agree between the compute shaders and the CPU,
+/// whether read back from a buffer or from a height map texture they were written into.
+/// Run with `cargo test -- --ignored` where there is an adapter, which may be a software one such as llvmpipe.
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn gpu_matches_cpu() {
+    let generator = GpuTerrainGenerator::headless().expect("No GPU adapter available");
+    let texture = generator.height_map_texture(200, 150);
+
+    let shapes = [
+        FalloffShape::Gaussian,
+        FalloffShape::Quadratic,
+        FalloffShape::Squircle { exponent: 4.0 },
+        FalloffShape::Ellipse {
+            aspect: 1.8,
+            rotation: 30.0,
+        },
+        FalloffShape::NoisyRadius {
+            amplitude: 0.3,
+            harmonics: 6,
+        },
+        FalloffShape::Ring {
+            radius: 1.2,
+            thickness: 0.3,
+        },
+        FalloffShape::Crescent {
+            shift: 0.5,
+            inner_radius: 0.7,
+            rotation: 120.0,
+        },
+    ];
+    for shape in shapes {
+        let pipeline = TerrainPipeline::new()
+            .with_stage(NoiseStage::new(vec![
+                (vec2(3.0, 3.0), 1.0),
+                (vec2(5.0, 5.0), 0.7),
+                (vec2(7.0, 7.0), 0.5),
+                (vec2(11.0, 11.0), 0.3),
+                (vec2(13.0, 13.0), 0.2),
+            ]))
+            .with_stage(NormaliseStage)
+            .with_stage(FalloffStage::new(vec2(0.45, 0.55), 0.25).with_shape(shape))
+            .with_stage(SeaLevelClampStage::new(0.2));
+
+        for seed in [1, 42] {
+            let (cpu, _) = pipeline.generate(200, 150, seed);
+            let (gpu, _) = generator.generate(&pipeline, 200, 150, seed).unwrap();
+            let mut written = Array2::zeros((150, 200));
+            let gpu_stages = generator
+                .apply(&pipeline, &mut written, seed, Some(&texture))
+                .unwrap();
+            assert_eq!(gpu_stages, 3);
+            pipeline.stages[gpu_stages].apply(&mut written, &mut TerrainLayers::default(), seed);
+
+            for (gpu, how) in [(gpu, "buffer"), (written, "texture")] {
+                let error = cpu
+                    .iter()
+                    .zip(gpu.iter())
+                    .fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+                assert!(
+                    error < 1.0e-4,
+                    "{:?} with seed {} differs by {} read back from a {}",
+                    shape,
+                    seed,
+                    error,
+                    how
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+/// Random positions within `extent` of the origin, the same every run.
+fn positions(seed: u64, extent: f32) -> Vec<Vec2> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    (0..500)
+        .map(|_| vec2(rng.gen_range(0.0..extent), rng.gen_range(0.0..extent)))
+        .collect()
+}
+
+/// Check an analytic value and gradient against the function's value and central differences.
+/// Central differences lose a few digits to rounding, so the gradients need only agree to about a percent.
+fn assert_gradient_matches(
+    name: &str,
+    position: Vec2,
+    function: impl Fn(Vec2) -> f32,
+    (value, gradient): (f32, Vec2),
+) {
+    let (expected_value, expected_gradient) = central_differences(&function, position);
+    assert!(
+        (value - expected_value).abs() <= 1.0e-5 * expected_value.abs().max(1.0),
+        "{} value at {} is {}, not {}",
+        name,
+        position,
+        value,
+        expected_value
+    );
+    assert!(
+        (gradient - expected_gradient).length() <= 1.0e-2 * expected_gradient.length().max(1.0),
+        "{} gradient at {} is {}, not {}",
+        name,
+        position,
+        gradient,
+        expected_gradient
+    );
+}
+
+fn assert_source_gradient_matches(name: &str, source: &dyn NoiseSource, positions: &[Vec2]) {
+    for &position in positions {
+        assert_gradient_matches(
+            name,
+            position,
+            |position| source.sample(position),
+            source.sample_with_gradient(position),
+        );
+    }
+}
+
+/// Perlin layers, periodic or not, should have exact gradients.
+#[test]
+fn perlin_gradients_match_central_differences() {
+    let layers = vec![(vec2(3.0, 3.0), 1.0), (vec2(7.0, 5.0), 0.5)];
+    for noise in [
+        PerlinNoise::periodic(4, layers.clone(), Vec2::ONE),
+        PerlinNoise::infinite(4, layers),
+    ] {
+        for &position in &positions(1, 1.0) {
+            for layer in &noise.layers {
+                assert_gradient_matches(
+                    "Perlin layer",
+                    position,
+                    |position| noise.sample_layer(layer, position),
+                    noise.sample_layer_with_gradient(layer, position),
+                );
+            }
+        }
+        assert_source_gradient_matches("Perlin", &noise, &positions(2, 1.0));
+    }
+}
+
+/// Simplex and OpenSimplex2 kernels should have exact gradients.
+#[test]
+fn simplex_gradients_match_central_differences() {
+    assert_source_gradient_matches("Simplex", &SimplexNoise::new(5), &positions(3, 4.0));
+    assert_source_gradient_matches(
+        "OpenSimplex2",
+        &OpenSimplex2Noise::new(5),
+        &positions(4, 4.0),
+    );
+}
+
+#[test]
+fn value_gradients_match_central_differences() {
+    assert_source_gradient_matches("Value", &ValueNoise::new(6), &positions(5, 4.0));
+}
+
+/// Worley gradients are exact away from cell borders, where the nearest or second nearest feature point changes
+/// and the gradient jumps, so positions where either changes within the central differences are skipped.
+#[test]
+fn worley_gradients_match_central_differences() {
+    for feature in [
+        WorleyFeature::F1,
+        WorleyFeature::F2,
+        WorleyFeature::Difference,
+    ] {
+        let noise = WorleyNoise::new(7, (8, 8), DistanceMetric::Euclidean).with_feature(feature);
+        let positions: Vec<Vec2> = positions(6, 1.0)
+            .into_iter()
+            .filter(|&position| {
+                let sample = noise.cells(position);
+                [
+                    vec2(1.0, 0.0),
+                    vec2(-1.0, 0.0),
+                    vec2(0.0, 1.0),
+                    vec2(0.0, -1.0),
+                ]
+                .into_iter()
+                .all(|direction| {
+                    // Offsets to the same point move with the position, in cells
+                    let shift = direction * 1.0e-4;
+                    let shifted = noise.cells(position + shift);
+                    let moved = shift * 8.0;
+                    (shifted.f1_delta - moved).abs_diff_eq(sample.f1_delta, 1.0e-3)
+                        && (shifted.f2_delta - moved).abs_diff_eq(sample.f2_delta, 1.0e-3)
+                })
+            })
+            .collect();
+        assert!(positions.len() > 400);
+        assert_source_gradient_matches(&format!("Worley {:?}", feature), &noise, &positions);
+    }
+}
+
+/// The Jacobian of a domain warp should match central differences of each component of the warped position,
+/// and the chain rule through it the gradient of the warped noise.
+#[test]
+fn domain_warp_gradients_match_central_differences() {
+    for iterations in [1, 3] {
+        let warp = DomainWarp::new(
+            Box::new(PerlinNoise::infinite(8, vec![(vec2(2.0, 2.0), 1.0)])),
+            Box::new(PerlinNoise::infinite(9, vec![(vec2(1.5, 1.5), 1.0)])),
+            0.2,
+        )
+        .with_iterations(iterations);
+        for &position in &positions(7, 1.0) {
+            let (warped, jacobian) = warp.warped_with_jacobian(position);
+            for (component, name) in [(0, "warped x"), (1, "warped y")] {
+                assert_gradient_matches(
+                    name,
+                    position,
+                    |position| warp.warped(position)[component],
+                    (warped[component], jacobian.row(component)),
+                );
+            }
+        }
+        assert_source_gradient_matches("DomainWarp", &warp, &positions(8, 1.0));
+    }
+}
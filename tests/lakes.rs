@@ -0,0 +1,44 @@
+use islands::prelude::*;
+use ndarray::{s, Array2};
+
+/// Only the depression that is both deep and wide enough should fill with a lake;
+/// a deep pit that is too small and a wide hollow that is too shallow should stay dry.
+#[test]
+fn large_deep_depressions_become_lakes() {
+    // Ground sloping gently down to the western edge of the map
+    let mut height_map = Array2::from_shape_fn((20, 30), |(_, xi)| 0.3 + 0.01 * xi as f32);
+    let lake = s![3..9, 5..11];
+    height_map.slice_mut(lake).fill(0.2);
+    height_map.slice_mut(s![12..15, 5..8]).fill(0.1);
+    height_map.slice_mut(s![11..17, 15..21]).fill(0.435);
+
+    let mut layers = TerrainLayers::default();
+    LakeStage {
+        sea_level: 0.0,
+        min_depth: 0.05,
+        min_area: 20,
+    }
+    .apply(&mut height_map, &mut layers, 0);
+
+    assert_eq!(layers.lakes.len(), 1);
+    let lake_info = &layers.lakes[0];
+    assert_eq!(lake_info.id, 1);
+    assert_eq!(lake_info.area, 36);
+    // Spills over the lowest point of its rim, on the downhill side
+    assert!((lake_info.spill_height - 0.34).abs() < 1.0e-6);
+    assert!((lake_info.max_depth - 0.14).abs() < 1.0e-6);
+
+    let lake_ids = layers.lake_ids.unwrap();
+    let water_surface = layers.water_surface.unwrap();
+    let mut expected_ids = Array2::zeros(height_map.dim());
+    expected_ids.slice_mut(lake).fill(1);
+    assert_eq!(lake_ids, expected_ids);
+    for (id, surface) in lake_ids.iter().zip(water_surface.iter()) {
+        let expected = if *id == 1 {
+            lake_info.spill_height
+        } else {
+            0.0
+        };
+        assert_eq!(*surface, expected);
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+use ndarray::{array, Array2};
+
+fn assert_closed(line: &[Vec2]) {
+    assert!(
+        line.len() > 2 && line[0] == line[line.len() - 1],
+        "{:?} is not closed",
+        line
+    );
+}
+
+/// A single high cell should be circled by one closed ring through the midpoints of its edges.
+#[test]
+fn single_cell_bump_gives_a_diamond() {
+    let height_map = array![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]];
+    let lines = contour_lines(&height_map, 0.5);
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_closed(line);
+    assert_eq!(line.len(), 5);
+    for point in [
+        vec2(1.0, 0.5),
+        vec2(1.5, 1.0),
+        vec2(1.0, 1.5),
+        vec2(0.5, 1.0),
+    ] {
+        assert!(line.contains(&point), "{:?} misses {}", line, point);
+    }
+}
+
+/// Both saddle cases should split into two segments, each cutting off one corner. Where the middle of the cell is
+/// above the level the high corners are joined across it and the low corners are cut off, and the other way round below.
+#[test]
+fn saddles_are_resolved_by_the_middle_of_the_cell() {
+    for height_map in [
+        array![[1.0, 0.0], [0.0, 1.0]],
+        array![[0.0, 1.0], [1.0, 0.0]],
+    ] {
+        for (level, cut_off_high) in [(0.4, false), (0.6, true)] {
+            let lines = contour_lines(&height_map, level);
+            assert_eq!(lines.len(), 2);
+            let mut corners: Vec<(usize, usize)> = lines
+                .iter()
+                .map(|line| {
+                    assert_eq!(line.len(), 2);
+                    let middle = (line[0] + line[1]) * 0.5;
+                    (middle.y.round() as usize, middle.x.round() as usize)
+                })
+                .collect();
+            corners.sort();
+            let mut expected: Vec<(usize, usize)> = height_map
+                .indexed_iter()
+                .filter(|(_, &height)| (height >= level) == cut_off_high)
+                .map(|(index, _)| index)
+                .collect();
+            expected.sort();
+            assert_eq!(corners, expected, "{:?} at {}", height_map, level);
+        }
+    }
+}
+
+/// A round island away from the edges of the map should give one closed ring at its coastline.
+#[test]
+fn island_gives_one_closed_ring() {
+    let centre = vec2(12.0, 10.0);
+    let height_map = Array2::from_shape_fn((20, 24), |(yi, xi)| {
+        1.0 - vec2(xi as f32, yi as f32).distance(centre) / 8.0
+    });
+    let lines = contour_lines(&height_map, 0.5);
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert_closed(line);
+    for point in line {
+        assert!(
+            (point.distance(centre) - 4.0).abs() < 0.1,
+            "{} is off the coast",
+            point
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use bevy::math::vec2;
+use islands::prelude::*;
+
+/// OpenSimplex2S noise should stay within its stated range, while still nearly reaching both ends of it.
+#[test]
+fn open_simplex2_stays_in_range() {
+    let (mut lowest, mut highest) = (f32::MAX, f32::MIN);
+    for seed in 0..4 {
+        let noise = OpenSimplex2Noise::new(seed);
+        for y in 0..400 {
+            for x in 0..400 {
+                let value = noise.sample(vec2(x as f32 * 0.0731 - 10.0, y as f32 * 0.0679 - 10.0));
+                lowest = lowest.min(value);
+                highest = highest.max(value);
+            }
+        }
+        let range = noise.range();
+        assert!(
+            range.contains(&lowest) && range.contains(&highest),
+            "values from {} to {} with seed {}",
+            lowest,
+            highest,
+            seed
+        );
+    }
+    assert!(
+        lowest < -0.9 && highest > 0.9,
+        "values from {} to {}",
+        lowest,
+        highest
+    );
+}
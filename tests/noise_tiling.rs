@@ -0,0 +1,53 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+
+/// Frequencies which do not fit a whole number of cells into the map.
+const LAYERS: [(Vec2, f32); 2] = [(Vec2::new(2.5, 1.5), 1.0), (Vec2::new(7.2, 7.2), 0.5)];
+
+/// Periodic noise should repeat every period, at fractional frequencies and negative positions alike.
+#[test]
+fn periodic_noise_repeats_every_period() {
+    let period = vec2(2.0, 3.0);
+    for algorithm in [
+        NoiseAlgorithm::Perlin,
+        NoiseAlgorithm::PerlinTorus,
+        NoiseAlgorithm::Worley {
+            metric: DistanceMetric::Euclidean,
+            feature: WorleyFeature::F1,
+        },
+    ] {
+        let noise = algorithm.layered(5, &LAYERS, NoiseTiling::Periodic { period });
+        for position in [vec2(0.1, 0.2), vec2(-1.3, 0.7), vec2(0.45, -2.6)] {
+            let (a, b) = (noise.sample(position), noise.sample(position + period));
+            assert!(
+                (a - b).abs() < 1.0e-4,
+                "{:?} does not repeat at {}",
+                algorithm,
+                position
+            );
+        }
+    }
+}
+
+/// Infinite Perlin noise should not repeat, and should be continuous through the origin.
+#[test]
+fn infinite_noise_is_defined_everywhere() {
+    let noise = NoiseAlgorithm::Perlin.layered(5, &LAYERS, NoiseTiling::Infinite);
+    let repeats = (1..20)
+        .map(|period| vec2(0.37, -0.21) + vec2(period as f32, 0.0))
+        .filter(|&position| {
+            (noise.sample(position) - noise.sample(vec2(0.37, -0.21))).abs() < 1.0e-6
+        })
+        .count();
+    assert_eq!(repeats, 0);
+
+    let step = vec2(1.0e-4, 1.0e-4);
+    assert!((noise.sample(-step) - noise.sample(step)).abs() < 1.0e-2);
+    let range = noise.range();
+    for y in -20..20 {
+        for x in -20..20 {
+            let value = noise.sample(vec2(x as f32, y as f32) * 0.37);
+            assert!(range.contains(&value));
+        }
+    }
+}
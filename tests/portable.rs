@@ -0,0 +1,43 @@
+use islands::prelude::*;
+use std::f32::consts::PI;
+
+/// Largest difference between two functions over evenly spaced inputs, relative to the size of the result.
+fn largest_error(
+    range: (f32, f32),
+    portable: impl Fn(f32) -> f32,
+    std: impl Fn(f32) -> f32,
+) -> f32 {
+    (0..=10_000)
+        .map(|step| range.0 + (range.1 - range.0) * step as f32 / 10_000.0)
+        .map(|x| (portable(x) - std(x)).abs() / std(x).abs().max(1.0))
+        .fold(0.0, f32::max)
+}
+
+/// The portable functions should agree with the standard library to within rounding.
+#[test]
+fn portable_functions_match_std() {
+    let tolerance = 4.0 * f32::EPSILON;
+    let checks = [
+        largest_error((-4.0 * PI, 4.0 * PI), |x| portable::sin_cos(x).0, f32::sin),
+        largest_error((-4.0 * PI, 4.0 * PI), |x| portable::sin_cos(x).1, f32::cos),
+        largest_error((-1.5, 1.5), portable::tan, f32::tan),
+        largest_error((-100.0, 80.0), portable::exp, f32::exp),
+        largest_error((1.0e-6, 1.0e6), portable::ln, f32::ln),
+        largest_error((0.0, 10.0), |x| portable::powf(x, 2.7), |x| x.powf(2.7)),
+        largest_error((0.0, 10.0), |x| portable::powf(x, 0.3), |x| x.powf(0.3)),
+        largest_error((-5.0, 5.0), |x| portable::atan2(x, 1.3), |x| x.atan2(1.3)),
+        largest_error(
+            (-5.0, 5.0),
+            |x| portable::atan2(1.3, x),
+            |x| 1.3f32.atan2(x),
+        ),
+        largest_error(
+            (-5.0, 5.0),
+            |x| portable::atan2(-0.7, x),
+            |x| (-0.7f32).atan2(x),
+        ),
+    ];
+    for (index, error) in checks.into_iter().enumerate() {
+        assert!(error <= tolerance, "check {} is off by {}", index, error);
+    }
+}
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+use islands::prelude::*;
+use std::fs;
+
+/// Every preset shipped in `assets/presets` should parse, and build a pipeline that generates finite terrain.
+#[test]
+fn presets_parse_and_build_pipelines() {
+    let mut paths: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/presets"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(".preset.ron"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let preset = TerrainPreset::from_ron(&fs::read(&path).unwrap())
+            .unwrap_or_else(|error| panic!("{} does not parse: {}", path.display(), error));
+        assert!(
+            !preset.colour_bands.is_empty(),
+            "{} has no colour bands",
+            path.display()
+        );
+        let pipeline = preset
+            .pipeline(&Assets::<Image>::default())
+            .with_sea_level(preset.sea_level);
+        assert_eq!(
+            pipeline.stages.len(),
+            preset.stages.len(),
+            "{} skipped stages",
+            path.display()
+        );
+        let (height_map, _) = pipeline.generate(64, 48, 1);
+        assert!(
+            height_map.iter().all(|height| height.is_finite()),
+            "{} generates non-finite heights",
+            path.display()
+        );
+    }
+}
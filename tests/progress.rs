@@ -0,0 +1,72 @@
+use bevy::math::vec2;
+use islands::prelude::*;
+use ndarray::Array2;
+use std::{sync::Arc, thread};
+
+fn hills() -> Array2<f32> {
+    TerrainPipeline::new()
+        .with_stage(NoiseStage::new(vec![(vec2(5.0, 5.0), 1.0)]))
+        .with_stage(NormaliseStage)
+        .generate(96, 64, 5)
+        .0
+}
+
+/// Long stages should check for cancellation as they go, leaving their work undone once cancelled.
+#[test]
+fn cancelled_stages_stop_early() {
+    let progress = GenerationProgress::default();
+    progress.cancel();
+    let stages: [(&str, Arc<dyn TerrainStage>); 4] = [
+        (
+            "hydraulic erosion",
+            Arc::new(HydraulicErosionStage::default()),
+        ),
+        ("thermal erosion", Arc::new(ThermalErosionStage::default())),
+        ("rivers", Arc::new(RiverStage::default())),
+        ("lakes", Arc::new(LakeStage::default())),
+    ];
+    for (name, stage) in stages {
+        let mut height_map = hills();
+        let mut layers = TerrainLayers::default();
+        stage.apply_with_progress(&mut height_map, &mut layers, 5, &progress);
+        assert_eq!(height_map, hills(), "cancelled {} changed the map", name);
+        assert!(
+            layers.rivers.is_none() && layers.lake_ids.is_none(),
+            "cancelled {} found water",
+            name
+        );
+    }
+}
+
+/// Cancelling part way through a stage should stop it there, with its progress reported along the way.
+#[test]
+fn cancelling_stops_within_a_stage() {
+    let pipeline = TerrainPipeline::new().with_stage(HydraulicErosionStage {
+        iterations: 2_000_000,
+        ..Default::default()
+    });
+    let progress = Arc::new(GenerationProgress::default());
+    let generation = {
+        let progress = progress.clone();
+        thread::spawn(move || {
+            let mut height_map = hills();
+            pipeline.apply_with_progress(
+                &mut height_map,
+                &mut TerrainLayers::default(),
+                5,
+                &progress,
+            )
+        })
+    };
+
+    while progress.fraction() == 0.0 {
+        thread::yield_now();
+    }
+    progress.cancel();
+    assert!(!generation.join().unwrap(), "generation was not cancelled");
+    assert!(
+        progress.fraction() < 1.0,
+        "the stage ran to the end, at {}",
+        progress.fraction()
+    );
+}
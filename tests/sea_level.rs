@@ -0,0 +1,70 @@
+use bevy::math::vec2;
+use islands::prelude::*;
+use ndarray::Array2;
+
+fn pipeline() -> TerrainPipeline {
+    TerrainPipeline::new()
+        .with_stage(NoiseStage::new(vec![
+            (vec2(3.0, 3.0), 1.0),
+            (vec2(7.0, 7.0), 0.4),
+        ]))
+        .with_stage(NormaliseStage)
+        .with_stage(FalloffStage::new(vec2(0.5, 0.5), 0.3))
+        .with_stage(ThermalErosionStage::default())
+        .with_stage(RiverStage {
+            threshold: 50.0,
+            ..Default::default()
+        })
+        .with_stage(LakeStage::default())
+}
+
+/// Rerunning only the stages from the first one depending on the sea level, over the height map from just before it,
+/// should give the same terrain as generating it all again at the new sea level.
+#[test]
+fn rerunning_sea_level_stages_matches_full_generation() {
+    let pipeline = pipeline();
+    let start = pipeline.sea_level_start();
+    assert_eq!(
+        start, 4,
+        "rivers should be the first stage using the sea level"
+    );
+
+    let (mut before, mut before_layers) = (Array2::zeros((64, 96)), TerrainLayers::default());
+    let progress = GenerationProgress::default();
+    assert!(pipeline.apply_stages_with_progress(
+        0..start,
+        &mut before,
+        &mut before_layers,
+        3,
+        &progress
+    ));
+
+    for sea_level in [0.2, 0.35, 0.5] {
+        let pipeline = pipeline.with_sea_level(sea_level);
+        let (expected, expected_layers) = pipeline.generate(96, 64, 3);
+
+        let (mut height_map, mut layers) = (before.clone(), before_layers.clone());
+        assert!(pipeline.apply_stages_with_progress(
+            start..pipeline.stages.len(),
+            &mut height_map,
+            &mut layers,
+            3,
+            &progress
+        ));
+        assert_eq!(
+            height_map, expected,
+            "heights differ at sea level {}",
+            sea_level
+        );
+        assert_eq!(
+            layers.rivers, expected_layers.rivers,
+            "rivers differ at sea level {}",
+            sea_level
+        );
+        assert_eq!(
+            layers.lake_ids, expected_layers.lake_ids,
+            "lakes differ at sea level {}",
+            sea_level
+        );
+    }
+}
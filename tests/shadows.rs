@@ -0,0 +1,72 @@
+use bevy::math::{vec3, Vec3};
+use islands::prelude::*;
+use ndarray::Array2;
+
+const WALL: usize = 10;
+const WALL_HEIGHT: f32 = 10.0;
+
+/// Flat ground with a wall across it, one cell thick and `WALL_HEIGHT` cells high.
+fn wall(across_x: bool) -> Array2<f32> {
+    Array2::from_shape_fn((64, 64), |(yi, xi)| {
+        let position = if across_x { xi } else { yi };
+        if position == WALL {
+            WALL_HEIGHT
+        } else {
+            0.0
+        }
+    })
+}
+
+/// The shadow behind a wall should reach `height / tan(elevation)` cells, for a sun shining along either axis
+/// from either side, and the ground in front of the wall should be lit.
+#[test]
+fn wall_casts_shadow_of_expected_length() {
+    for elevation in [45.0f32, 26.565_05, 60.0] {
+        let rise = elevation.to_radians().tan();
+        let length = WALL_HEIGHT / rise;
+        for (across_x, side) in [(true, -1.0), (true, 1.0), (false, -1.0), (false, 1.0)] {
+            let towards_light = if across_x {
+                vec3(side, 0.0, 0.0)
+            } else {
+                vec3(0.0, side, 0.0)
+            };
+            let visibility =
+                directional_light_visibility(&wall(across_x), towards_light + Vec3::Z * rise, 0.01);
+            let (height, width) = visibility.dim();
+            for yi in 0..height {
+                for xi in 0..width {
+                    let position = if across_x { xi } else { yi };
+                    let behind = (WALL as f32 - position as f32) * side;
+                    let lit = visibility[(yi, xi)];
+                    if behind >= 1.0 && behind < length - 0.5 {
+                        assert_eq!(lit, 0.0, "lit {} behind at {}°", behind, elevation);
+                    } else if behind > length + 0.5 || behind <= 0.0 {
+                        assert_eq!(lit, 1.0, "shadowed {} behind at {}°", behind, elevation);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The bottom of a pit should see less of the sky than the top of a peak, which, like open ground, sees all of it.
+#[test]
+fn pit_is_more_occluded_than_peak() {
+    let bump = |centre: (f32, f32), depth: f32| {
+        move |(yi, xi): (usize, usize)| {
+            let distance = (yi as f32 - centre.0).hypot(xi as f32 - centre.1);
+            depth * (1.0 - distance / 6.0).max(0.0)
+        }
+    };
+    let (pit, peak) = (bump((16.0, 16.0), -3.0), bump((16.0, 48.0), 3.0));
+    let heights = Array2::from_shape_fn((32, 64), |index| pit(index) + peak(index));
+    let occlusion = ambient_occlusion(&heights, 8, 1.0);
+
+    let (pit, peak, open) = (occlusion[(16, 16)], occlusion[(16, 48)], occlusion[(2, 32)]);
+    assert!(pit < 0.7, "pit sees {} of the sky", pit);
+    assert_eq!(peak, 1.0);
+    assert_eq!(open, 1.0);
+    assert!(occlusion
+        .iter()
+        .all(|&visible| (0.0..=1.0).contains(&visible)));
+}
@@ -0,0 +1,51 @@
+use bevy::math::{vec2, Vec2};
+use islands::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+const METRICS: [DistanceMetric; 3] = [
+    DistanceMetric::Euclidean,
+    DistanceMetric::Manhattan,
+    DistanceMetric::Chebyshev,
+];
+
+/// F1 should vanish on every feature point, which should be nearest to itself.
+#[test]
+fn f1_is_zero_at_feature_points() {
+    for metric in METRICS {
+        let noise = WorleyNoise::new(11, (6, 4), metric);
+        let (height, width) = noise.points.dim();
+        for ((yi, xi), point) in noise.points.indexed_iter() {
+            let position =
+                (vec2(xi as f32, yi as f32) + *point) / vec2(width as f32, height as f32);
+            let sample = noise.cells(position);
+            assert!(sample.f1 < 1.0e-5, "F1 is {} at {}", sample.f1, position);
+            assert_eq!(sample.cell_id, (yi * width + xi) as u32);
+            assert!(sample.f2 > sample.f1);
+            assert_eq!(noise.sample(position), sample.f1);
+        }
+    }
+}
+
+/// The second nearest point is never nearer than the nearest, so `F2 - F1` should never be negative.
+#[test]
+fn difference_is_not_negative() {
+    let mut rng = ChaCha8Rng::seed_from_u64(12);
+    let positions: Vec<Vec2> = (0..2000)
+        .map(|_| vec2(rng.gen_range(-1.0..2.0), rng.gen_range(-1.0..2.0)))
+        .collect();
+    for metric in METRICS {
+        let noise = WorleyNoise::new(13, (7, 5), metric).with_feature(WorleyFeature::Difference);
+        for &position in &positions {
+            let value = noise.sample(position);
+            assert!(
+                value >= 0.0,
+                "{:?} difference is {} at {}",
+                metric,
+                value,
+                position
+            );
+            assert!(noise.range().contains(&value));
+        }
+    }
+}